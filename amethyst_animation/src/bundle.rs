@@ -2,6 +2,7 @@ use std::{hash::Hash, marker};
 
 use resources::AnimationSampling;
 use skinning::VertexSkinningSystem;
+use tween::{TweenSystem, Tweenable};
 
 use amethyst_core::{
     specs::prelude::{Component, DispatcherBuilder},
@@ -152,3 +153,47 @@ where
             .build(builder)
     }
 }
+
+/// Bundle for tweens of a component type.
+///
+/// Will add `TweenSystem<C>` with the given name.
+///
+/// ### Type parameters:
+///
+/// - `C`: the component type that the tweens apply to
+pub struct TweenBundle<'a, C> {
+    name: &'a str,
+    dep: &'a [&'a str],
+    m: marker::PhantomData<C>,
+}
+
+impl<'a, C> TweenBundle<'a, C> {
+    /// Create a new tween bundle
+    ///
+    /// ### Parameters:
+    ///
+    /// - `name`: name of the `TweenSystem`
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            dep: &[],
+            m: marker::PhantomData,
+        }
+    }
+
+    /// Set dependencies for the `TweenSystem`
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, 'b, 'c, C> SystemBundle<'a, 'b> for TweenBundle<'c, C>
+where
+    C: Tweenable,
+{
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add(TweenSystem::<C>::new(), self.name, self.dep);
+        Ok(())
+    }
+}
@@ -53,6 +53,8 @@ extern crate amethyst_core;
 #[macro_use]
 extern crate amethyst_derive;
 extern crate amethyst_renderer;
+#[cfg(feature = "ui")]
+extern crate amethyst_ui;
 #[macro_use]
 extern crate derivative;
 extern crate fnv;
@@ -69,7 +71,7 @@ extern crate serde;
 extern crate thread_profiler;
 
 pub use self::{
-    bundle::{AnimationBundle, SamplingBundle, VertexSkinningBundle},
+    bundle::{AnimationBundle, SamplingBundle, TweenBundle, VertexSkinningBundle},
     material::{MaterialChannel, MaterialPrimitive},
     prefab::{AnimatablePrefab, AnimationHierarchyPrefab, AnimationPrefab, AnimationSetPrefab},
     resources::{
@@ -83,9 +85,13 @@ pub use self::{
         AnimationControlSystem, AnimationProcessor, SamplerInterpolationSystem, SamplerProcessor,
     },
     transform::TransformChannel,
+    tween::{Easing, Tween, TweenEvent, TweenLoop, TweenSystem, Tweenable},
     util::{get_animation_set, SamplerPrimitive},
 };
 
+#[cfg(feature = "ui")]
+pub use self::tween::{UiTextChannel, UiTransformChannel};
+
 pub use minterpolate::{InterpolationFunction, InterpolationPrimitive};
 
 mod bundle;
@@ -96,4 +102,5 @@ mod skinning;
 mod sprite;
 mod systems;
 mod transform;
+mod tween;
 mod util;
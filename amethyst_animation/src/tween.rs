@@ -0,0 +1,508 @@
+//! Tweens, animating a single field of a component between two values with an easing curve.
+
+use std::{f32::consts::PI, fmt::Debug, marker, mem};
+
+use minterpolate::InterpolationPrimitive;
+
+use amethyst_core::{
+    shrev::EventChannel,
+    specs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, System, Write, WriteStorage,
+    },
+    Time, Transform,
+};
+#[cfg(feature = "ui")]
+use amethyst_ui::{UiText, UiTransform};
+
+use {resources::AnimationSampling, transform::TransformChannel, util::SamplerPrimitive};
+
+/// Easing curve of a `Tween`, mapping the elapsed fraction of the tween to the fraction of the
+/// distance between the start and end values.
+///
+/// Elastic and bounce curves overshoot, so the tweened value goes beyond the start or end value
+/// for a moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Quadratic acceleration from zero speed.
+    QuadIn,
+    /// Quadratic deceleration to zero speed.
+    QuadOut,
+    /// Quadratic acceleration until halfway, then deceleration.
+    QuadInOut,
+    /// Cubic acceleration from zero speed.
+    CubicIn,
+    /// Cubic deceleration to zero speed.
+    CubicOut,
+    /// Cubic acceleration until halfway, then deceleration.
+    CubicInOut,
+    /// Oscillates with a growing amplitude before reaching the end value.
+    ElasticIn,
+    /// Overshoots the end value and oscillates around it.
+    ElasticOut,
+    /// Elastic in until halfway, then elastic out.
+    ElasticInOut,
+    /// Bounces with a growing height before reaching the end value.
+    BounceIn,
+    /// Bounces on the end value like a falling ball.
+    BounceOut,
+    /// Bounce in until halfway, then bounce out.
+    BounceInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Applies the curve to `t`, which goes from 0 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        use self::Easing::*;
+
+        match self {
+            Linear => t,
+            QuadIn => t * t,
+            QuadOut => t * (2. - t),
+            QuadInOut => in_out(t, QuadIn),
+            CubicIn => t * t * t,
+            CubicOut => 1. - CubicIn.apply(1. - t),
+            CubicInOut => in_out(t, CubicIn),
+            ElasticIn => 1. - ElasticOut.apply(1. - t),
+            ElasticOut => {
+                if t <= 0. || t >= 1. {
+                    t
+                } else {
+                    2f32.powf(-10. * t) * ((t - 0.075) * 2. * PI / 0.3).sin() + 1.
+                }
+            }
+            ElasticInOut => in_out(t, ElasticIn),
+            BounceIn => 1. - BounceOut.apply(1. - t),
+            BounceOut => bounce_out(t),
+            BounceInOut => in_out(t, BounceIn),
+        }
+    }
+}
+
+/// Plays the `ease_in` curve on the first half, and its mirror on the second half.
+fn in_out(t: f32, ease_in: Easing) -> f32 {
+    if t < 0.5 {
+        ease_in.apply(2. * t) / 2.
+    } else {
+        1. - ease_in.apply(2. - 2. * t) / 2.
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984_375
+    }
+}
+
+/// How a `Tween` repeats once it reached its end value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TweenLoop {
+    /// Play the tween once.
+    Once,
+    /// Restart from the start value, the given number of times in total or forever.
+    Repeat(Option<u32>),
+    /// Go back and forth between the start and end values, each direction counting as one time.
+    PingPong(Option<u32>),
+}
+
+impl Default for TweenLoop {
+    fn default() -> Self {
+        TweenLoop::Once
+    }
+}
+
+/// Event sent by the `TweenSystem` to the `EventChannel<TweenEvent>` resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweenEvent {
+    /// A looping tween went through its end value and started over.
+    Looped(Entity),
+    /// A tween reached its end value for the last time, it won't change the component anymore.
+    Completed(Entity),
+}
+
+/// A component whose fields can be animated by a `Tween`.
+///
+/// It is implemented for `Transform`, and with the `ui` feature for `UiTransform` and `UiText`.
+/// `Material` isn't tweenable, as it has no tint color to animate; its texture offsets can be
+/// animated with an `Animation` using `MaterialChannel`s instead.
+pub trait Tweenable: Component + Send + Sync {
+    /// The fields of the component which can be tweened.
+    type Channel: Debug + Clone + Send + Sync + 'static;
+
+    /// Returns the current value of a field.
+    fn tween_value(&self, channel: &Self::Channel) -> SamplerPrimitive<f32>;
+
+    /// Sets the value of a field.
+    fn set_tween_value(&mut self, channel: &Self::Channel, value: &SamplerPrimitive<f32>);
+}
+
+/// Animates a field of the component `C` of the same entity from a start value to an end value.
+///
+/// This is a lightweight alternative to an `Animation` for simple movements, like sliding a UI
+/// element in or making an object bob. The `TweenSystem<C>` advances the tween every frame, and
+/// sends `TweenEvent`s when it loops or completes. Completed tweens are kept on the entity, and can
+/// be restarted or removed.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// world
+///     .create_entity()
+///     .with(transform)
+///     .with(
+///         Tween::<Transform>::new(TransformChannel::Translation, [0., 0., 0.], [0., 1., 0.], 0.5)
+///             .with_easing(Easing::QuadInOut)
+///             .with_loop(TweenLoop::PingPong(None)),
+///     ).build();
+/// ```
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub struct Tween<C>
+where
+    C: Tweenable,
+{
+    channel: C::Channel,
+    from: SamplerPrimitive<f32>,
+    to: SamplerPrimitive<f32>,
+    duration: f32,
+    easing: Easing,
+    looping: TweenLoop,
+    elapsed: f32,
+    cycles: u32,
+    completed: bool,
+}
+
+impl<C> Tween<C>
+where
+    C: Tweenable,
+{
+    /// Creates a new linear tween of the given field from `from` to `to`, over `duration` seconds.
+    pub fn new<V>(channel: C::Channel, from: V, to: V, duration: f32) -> Self
+    where
+        V: Into<SamplerPrimitive<f32>>,
+    {
+        Tween {
+            channel,
+            from: from.into(),
+            to: to.into(),
+            // A zero duration would make the elapsed fraction undefined.
+            duration: duration.max(1.0e-6),
+            easing: Easing::Linear,
+            looping: TweenLoop::Once,
+            elapsed: 0.,
+            cycles: 0,
+            completed: false,
+        }
+    }
+
+    /// Creates a new linear tween of the given field from its current value in `component` to
+    /// `to`, over `duration` seconds.
+    pub fn from_current<V>(component: &C, channel: C::Channel, to: V, duration: f32) -> Self
+    where
+        V: Into<SamplerPrimitive<f32>>,
+    {
+        let from = component.tween_value(&channel);
+        Tween::new(channel, from, to.into(), duration)
+    }
+
+    /// Sets the easing curve.
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Sets how the tween repeats.
+    pub fn with_loop(mut self, looping: TweenLoop) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Returns the tweened field.
+    pub fn channel(&self) -> &C::Channel {
+        &self.channel
+    }
+
+    /// Returns true once the tween reached its end value for the last time.
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    /// Returns the fraction of the current repetition which has elapsed, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        (self.elapsed / self.duration).min(1.)
+    }
+
+    /// Starts the tween over from the start value.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.;
+        self.cycles = 0;
+        self.completed = false;
+    }
+
+    /// Returns the tweened value at the current time.
+    pub fn value(&self) -> SamplerPrimitive<f32> {
+        let mut t = self.progress();
+        if let TweenLoop::PingPong(_) = self.looping {
+            if self.cycles % 2 == 1 {
+                t = 1. - t;
+            }
+        }
+        let eased = self.easing.apply(t);
+        self.from.add(&self.to.sub(&self.from).mul(eased))
+    }
+
+    /// Advances the tween by `delta` seconds, and returns the event to send, if any.
+    fn advance(&mut self, delta: f32, entity: Entity) -> Option<TweenEvent> {
+        if self.completed {
+            return None;
+        }
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            return None;
+        }
+        let count = match self.looping {
+            TweenLoop::Once => Some(1),
+            TweenLoop::Repeat(count) | TweenLoop::PingPong(count) => count,
+        };
+        let passed = (self.elapsed / self.duration) as u32;
+        if let Some(count) = count {
+            if self.cycles + passed >= count {
+                // Stay at the end of the last repetition.
+                self.cycles = count.max(1) - 1;
+                self.elapsed = self.duration;
+                self.completed = true;
+                return Some(TweenEvent::Completed(entity));
+            }
+        }
+        self.cycles += passed;
+        self.elapsed -= passed as f32 * self.duration;
+        Some(TweenEvent::Looped(entity))
+    }
+}
+
+impl<C> Component for Tween<C>
+where
+    C: Tweenable,
+{
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Advances the `Tween<C>` components and applies their value to the `C` component of the same
+/// entity.
+///
+/// Tweens run on the scaled `Time::delta_seconds`. `TweenEvent`s are sent to the
+/// `EventChannel<TweenEvent>` resource.
+///
+/// ### Type parameters:
+///
+/// - `C`: the component type that the tweens apply to
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct TweenSystem<C> {
+    m: marker::PhantomData<C>,
+}
+
+impl<C> TweenSystem<C> {
+    /// Creates a new `TweenSystem`
+    pub fn new() -> Self {
+        TweenSystem {
+            m: marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, C> System<'a> for TweenSystem<C>
+where
+    C: Tweenable,
+{
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        WriteStorage<'a, Tween<C>>,
+        WriteStorage<'a, C>,
+        Write<'a, EventChannel<TweenEvent>>,
+    );
+
+    fn run(&mut self, (entities, time, mut tweens, mut components, mut events): Self::SystemData) {
+        for (entity, tween, component) in (&*entities, &mut tweens, &mut components).join() {
+            if tween.is_completed() {
+                continue;
+            }
+            let event = tween.advance(time.delta_seconds(), entity);
+            component.set_tween_value(&tween.channel, &tween.value());
+            if let Some(event) = event {
+                events.single_write(event);
+            }
+        }
+    }
+}
+
+impl Tweenable for Transform {
+    type Channel = TransformChannel;
+
+    fn tween_value(&self, channel: &TransformChannel) -> SamplerPrimitive<f32> {
+        self.current_sample(channel, &())
+    }
+
+    /// Rotations are interpolated linearly then normalized, which slightly changes the angular
+    /// speed compared to a spherical interpolation.
+    fn set_tween_value(&mut self, channel: &TransformChannel, value: &SamplerPrimitive<f32>) {
+        if mem::discriminant(value) != mem::discriminant(&self.tween_value(channel)) {
+            warn!(
+                "Ignoring tween value {:?} of the wrong type for Transform channel {:?}",
+                value, channel
+            );
+            return;
+        }
+        self.apply_sample(channel, value, &());
+    }
+}
+
+/// Fields of a `UiTransform` which can be tweened.
+#[cfg(feature = "ui")]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiTransformChannel {
+    /// The local x, y and z coordinates.
+    Translation,
+    /// The width and height.
+    Size,
+}
+
+#[cfg(feature = "ui")]
+impl Tweenable for UiTransform {
+    type Channel = UiTransformChannel;
+
+    fn tween_value(&self, channel: &UiTransformChannel) -> SamplerPrimitive<f32> {
+        match channel {
+            UiTransformChannel::Translation => {
+                SamplerPrimitive::Vec3([self.local_x, self.local_y, self.local_z])
+            }
+            UiTransformChannel::Size => SamplerPrimitive::Vec2([self.width, self.height]),
+        }
+    }
+
+    fn set_tween_value(&mut self, channel: &UiTransformChannel, value: &SamplerPrimitive<f32>) {
+        match (channel, *value) {
+            (UiTransformChannel::Translation, SamplerPrimitive::Vec3(d)) => {
+                self.local_x = d[0];
+                self.local_y = d[1];
+                self.local_z = d[2];
+            }
+            (UiTransformChannel::Size, SamplerPrimitive::Vec2(d)) => {
+                self.width = d[0];
+                self.height = d[1];
+            }
+            (channel, value) => warn!(
+                "Ignoring tween value {:?} of the wrong type for UiTransform channel {:?}",
+                value, channel
+            ),
+        }
+    }
+}
+
+/// Fields of a `UiText` which can be tweened.
+#[cfg(feature = "ui")]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UiTextChannel {
+    /// The RGBA color of the text.
+    Color,
+}
+
+#[cfg(feature = "ui")]
+impl Tweenable for UiText {
+    type Channel = UiTextChannel;
+
+    fn tween_value(&self, _: &UiTextChannel) -> SamplerPrimitive<f32> {
+        SamplerPrimitive::Vec4(self.color)
+    }
+
+    fn set_tween_value(&mut self, _: &UiTextChannel, value: &SamplerPrimitive<f32>) {
+        match *value {
+            SamplerPrimitive::Vec4(color) => self.color = color,
+            value => warn!(
+                "Ignoring tween value {:?} of the wrong type for the UiText color",
+                value
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::specs::prelude::World;
+
+    use super::*;
+
+    fn x(value: SamplerPrimitive<f32>) -> f32 {
+        match value {
+            SamplerPrimitive::Vec3(d) => d[0],
+            _ => panic!("Unexpected tween value"),
+        }
+    }
+
+    #[test]
+    fn easings_start_and_end_on_the_values() {
+        use self::Easing::*;
+
+        for easing in &[
+            Linear,
+            QuadIn,
+            QuadOut,
+            QuadInOut,
+            CubicIn,
+            CubicOut,
+            CubicInOut,
+            ElasticIn,
+            ElasticOut,
+            ElasticInOut,
+            BounceIn,
+            BounceOut,
+            BounceInOut,
+        ] {
+            assert!(easing.apply(0.).abs() < 1.0e-5, "{:?}", easing);
+            assert!((easing.apply(1.) - 1.).abs() < 1.0e-5, "{:?}", easing);
+            assert!((easing.apply(0.5) - 0.5).abs() < 0.5, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn ping_pong_tween_completes() {
+        let entity = World::new().entities().create();
+        let mut tween =
+            Tween::<Transform>::new(TransformChannel::Translation, [0.; 3], [2., 0., 0.], 1.)
+                .with_loop(TweenLoop::PingPong(Some(2)));
+
+        assert_eq!(None, tween.advance(0.5, entity));
+        assert_eq!(1., x(tween.value()));
+        assert_eq!(
+            Some(TweenEvent::Looped(entity)),
+            tween.advance(0.75, entity)
+        );
+        assert_eq!(1.5, x(tween.value()));
+        assert_eq!(
+            Some(TweenEvent::Completed(entity)),
+            tween.advance(1., entity)
+        );
+        assert_eq!(0., x(tween.value()));
+        assert!(tween.is_completed());
+        assert_eq!(None, tween.advance(1., entity));
+    }
+}
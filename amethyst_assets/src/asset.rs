@@ -55,6 +55,15 @@ pub trait Format<A: Asset>: Send + 'static {
         options: Self::Options,
         create_reload: bool,
     ) -> Result<FormatValue<A>>;
+
+    /// Returns the names of the assets the asset with the given name depends on, which are
+    /// recorded in the `DependencyGraph` of the `Loader` before importing it, and again every time
+    /// it is hot reloaded.
+    ///
+    /// The default implementation declares no dependencies.
+    fn dependencies(&self, _name: &str, _options: &Self::Options) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 /// The `Ok` return value of `Format::import` for a given asset type `A`.
@@ -87,6 +96,13 @@ pub trait SimpleFormat<A: Asset> {
 
     /// Produces asset data from given bytes.
     fn import(&self, bytes: Vec<u8>, options: Self::Options) -> Result<A::Data>;
+
+    /// Returns the names of the assets the asset with the given name depends on.
+    ///
+    /// See `Format::dependencies`.
+    fn dependencies(&self, _name: &str, _options: &Self::Options) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 impl<A, T> Format<A> for T
@@ -121,4 +137,8 @@ where
             Ok(FormatValue::data(data))
         }
     }
+
+    fn dependencies(&self, name: &str, options: &Self::Options) -> Vec<String> {
+        T::dependencies(self, name, options)
+    }
 }
@@ -0,0 +1,209 @@
+//! Defines the `DependencyGraph` of the assets loaded by the `Loader`.
+
+use std::cell::RefCell;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+thread_local! {
+    /// The name of the asset processed on this thread, which the assets loaded meanwhile are
+    /// dependencies of.
+    static PROCESSING: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Calls `f` while processing the asset with the given name, so that the assets loaded by `f`
+/// (e.g. in `PrefabData::load_sub_assets`) are recorded as its dependencies.
+pub(crate) fn processing<F, R>(name: &str, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = PROCESSING.with(|processing| processing.replace(Some(name.to_owned())));
+    let result = f();
+    PROCESSING.with(|processing| *processing.borrow_mut() = previous);
+    result
+}
+
+/// Returns the name of the asset processed on this thread, if any.
+pub(crate) fn processed_asset() -> Option<String> {
+    PROCESSING.with(|processing| processing.borrow().clone())
+}
+
+/// Graph of the dependencies between assets, identified by their names.
+///
+/// An asset depends on another one if it can't be used without it, or if it was created from its
+/// data. Dependencies are either declared by the `Format` of an asset, or recorded when assets
+/// are loaded while processing another asset, like the sub assets of a `Prefab`.
+///
+/// When an asset is hot reloaded, the assets which directly depend on it are reloaded as well,
+/// which in turn reloads their own dependents. To avoid endless reloads, the graph never contains
+/// cycles.
+///
+/// The graph of the `Loader` can be inspected with `Loader::dependency_graph`.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    dependencies: FnvHashMap<String, FnvHashSet<String>>,
+    dependents: FnvHashMap<String, FnvHashSet<String>>,
+    /// Assets which need to be reloaded because of a dependency, with the frame they were marked
+    /// in.
+    stale: FnvHashMap<String, u64>,
+}
+
+impl DependencyGraph {
+    /// Creates an empty dependency graph.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records that `dependent` depends on `dependency`.
+    ///
+    /// Returns false, without adding the dependency, if `dependency` already depends on
+    /// `dependent`, directly or not, or if both are the same asset.
+    pub fn add_dependency<D, N>(&mut self, dependent: D, dependency: N) -> bool
+    where
+        D: Into<String>,
+        N: Into<String>,
+    {
+        let dependent = dependent.into();
+        let dependency = dependency.into();
+        if dependent == dependency || self.depends_on(&dependency, &dependent) {
+            return false;
+        }
+
+        self.dependents
+            .entry(dependency.clone())
+            .or_insert_with(Default::default)
+            .insert(dependent.clone());
+        self.dependencies
+            .entry(dependent)
+            .or_insert_with(Default::default)
+            .insert(dependency);
+        true
+    }
+
+    /// Removes all the dependencies of an asset.
+    pub fn remove_dependencies(&mut self, dependent: &str) {
+        for dependency in self.dependencies.remove(dependent).unwrap_or_default() {
+            let empty = self
+                .dependents
+                .get_mut(&dependency)
+                .map_or(false, |dependents| {
+                    dependents.remove(dependent);
+                    dependents.is_empty()
+                });
+            if empty {
+                self.dependents.remove(&dependency);
+            }
+        }
+    }
+
+    /// Returns the assets the asset directly depends on.
+    pub fn dependencies(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.dependencies
+            .get(name)
+            .into_iter()
+            .flat_map(|names| names.iter().map(String::as_str))
+    }
+
+    /// Returns the assets which directly depend on the asset.
+    pub fn dependents(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.dependents
+            .get(name)
+            .into_iter()
+            .flat_map(|names| names.iter().map(String::as_str))
+    }
+
+    /// Returns true if `dependent` depends on `dependency`, directly or through other assets.
+    pub fn depends_on(&self, dependent: &str, dependency: &str) -> bool {
+        let mut visited = FnvHashSet::default();
+        let mut open = vec![dependent];
+        while let Some(name) = open.pop() {
+            for next in self.dependencies(name) {
+                if next == dependency {
+                    return true;
+                }
+                if visited.insert(next) {
+                    open.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns all the dependencies as `(dependent, dependency)` pairs.
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.dependencies.iter().flat_map(|(dependent, names)| {
+            names
+                .iter()
+                .map(move |dependency| (dependent.as_str(), dependency.as_str()))
+        })
+    }
+
+    /// Marks the direct dependents of a reloaded asset as needing a reload.
+    pub(crate) fn mark_dependents_stale(&mut self, name: &str, frame_number: u64) {
+        if let Some(dependents) = self.dependents.get(name) {
+            for dependent in dependents {
+                self.stale.insert(dependent.clone(), frame_number);
+            }
+        }
+    }
+
+    /// Returns true if some assets need a reload because of a dependency.
+    pub(crate) fn has_stale(&self) -> bool {
+        !self.stale.is_empty()
+    }
+
+    /// Returns true, and unmarks the asset, if it needs a reload because of a dependency.
+    pub(crate) fn take_stale(&mut self, name: &str) -> bool {
+        self.stale.remove(name).is_some()
+    }
+
+    /// Unmarks the assets no storage reloaded in the frame they were marked in or the next one,
+    /// because no reload object was stored for them.
+    pub(crate) fn expire_stale(&mut self, frame_number: u64) {
+        self.stale.retain(|_, marked| *marked + 1 >= frame_number);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{processed_asset, processing, DependencyGraph};
+
+    #[test]
+    fn dependencies_without_cycles() {
+        let mut graph = DependencyGraph::new();
+        assert!(graph.add_dependency("level.ron", "hero.ron"));
+        assert!(graph.add_dependency("hero.ron", "hero.png"));
+        assert!(!graph.add_dependency("hero.png", "level.ron"));
+        assert!(!graph.add_dependency("hero.png", "hero.png"));
+
+        assert!(graph.depends_on("level.ron", "hero.png"));
+        assert_eq!(
+            vec!["hero.ron"],
+            graph.dependents("hero.png").collect::<Vec<_>>()
+        );
+        assert_eq!(2, graph.edges().count());
+
+        graph.mark_dependents_stale("hero.png", 5);
+        assert!(graph.take_stale("hero.ron"));
+        assert!(!graph.take_stale("hero.ron"));
+        graph.mark_dependents_stale("hero.ron", 5);
+        graph.expire_stale(6);
+        assert!(graph.has_stale());
+        graph.expire_stale(7);
+        assert!(!graph.has_stale());
+
+        graph.remove_dependencies("hero.ron");
+        assert!(!graph.depends_on("level.ron", "hero.png"));
+        assert_eq!(0, graph.dependents("hero.png").count());
+    }
+
+    #[test]
+    fn records_processed_asset() {
+        assert_eq!(None, processed_asset());
+        let inner = processing("prefab.ron", || {
+            processing("other.ron", || ());
+            processed_asset()
+        });
+        assert_eq!(Some("prefab.ron".to_owned()), inner);
+        assert_eq!(None, processed_asset());
+    }
+}
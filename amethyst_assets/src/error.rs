@@ -1,9 +1,10 @@
-use std::{str::Utf8Error, string::FromUtf8Error};
+use std::{io::Error as IoError, str::Utf8Error, string::FromUtf8Error};
 
 error_chain! {
     foreign_links {
         FromUtf8(FromUtf8Error) #[doc = "Wraps a UTF-8 error"];
         Utf8(Utf8Error) #[doc = "Wraps a UTF-8 error"];
+        Io(IoError) #[doc = "Wraps an I/O error"];
     }
 
     errors {
@@ -16,10 +16,12 @@ extern crate crossbeam;
 extern crate derivative;
 #[macro_use]
 extern crate error_chain;
+extern crate flate2;
 extern crate fnv;
 extern crate hibitset;
 #[macro_use]
 extern crate log;
+extern crate notify;
 extern crate parking_lot;
 extern crate rayon;
 extern crate ron;
@@ -40,19 +42,21 @@ pub use formats::JsonFormat;
 pub use {
     asset::{Asset, Format, FormatValue, SimpleFormat},
     cache::Cache,
+    dependency::DependencyGraph,
     error::{Error, ErrorKind, Result, ResultExt},
     formats::RonFormat,
     helper::AssetLoaderSystemData,
     loader::Loader,
     prefab::{AssetPrefab, Prefab, PrefabData, PrefabError, PrefabLoader, PrefabLoaderSystem},
-    progress::{Completion, Progress, ProgressCounter, Tracker},
+    progress::{AssetErrorMeta, Completion, Progress, ProgressCounter, Tracker},
     reload::{HotReloadBundle, HotReloadStrategy, HotReloadSystem, Reload, SingleFile},
-    source::{Directory, Source},
+    source::{build_pak, Directory, Layered, MemorySource, Pak, Source},
     storage::{AssetStorage, Handle, ProcessingState, Processor, WeakHandle},
 };
 
 mod asset;
 mod cache;
+mod dependency;
 mod error;
 mod formats;
 mod helper;
@@ -1,15 +1,18 @@
-use std::{borrow::Borrow, hash::Hash, path::PathBuf, sync::Arc};
+use std::{borrow::Borrow, hash::Hash, ops::Deref, path::PathBuf, sync::Arc};
 
 use fnv::FnvHashMap;
+use parking_lot::RwLock;
 use rayon::ThreadPool;
 
 use {
+    dependency::{self, DependencyGraph},
     storage::{AssetStorage, Handle, Processed},
     Asset, Directory, ErrorKind, Format, FormatValue, Progress, ResultExt, Source,
 };
 
 /// The asset loader, holding the sources and a reference to the `ThreadPool`.
 pub struct Loader {
+    dependencies: Arc<RwLock<DependencyGraph>>,
     directory: Arc<Directory>,
     hot_reload: bool,
     pool: Arc<ThreadPool>,
@@ -24,6 +27,7 @@ impl Loader {
         P: Into<PathBuf>,
     {
         Loader {
+            dependencies: Default::default(),
             directory: Arc::new(Directory::new(directory)),
             hot_reload: true,
             pool,
@@ -120,6 +124,16 @@ impl Loader {
 
         let handle = storage.allocate();
 
+        {
+            let mut dependencies = self.dependencies.write();
+            if let Some(dependent) = dependency::processed_asset() {
+                dependencies.add_dependency(dependent, name.clone());
+            }
+            for dependency in format.dependencies(&name, &options) {
+                dependencies.add_dependency(name.clone(), dependency);
+            }
+        }
+
         debug!(
             "{:?}: Loading asset {:?} with format {:?} from source {:?} (handle id: {:?})",
             A::NAME,
@@ -187,6 +201,43 @@ impl Loader {
         handle
     }
 
+    /// Returns the directories on disk the default directory and the added sources read their
+    /// files from.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        let mut directories = self.directory.directories();
+        directories.extend(
+            self.sources
+                .values()
+                .flat_map(|source| source.directories()),
+        );
+        directories
+    }
+
+    /// Records that the asset named `dependent` depends on the asset named `dependency`, so that
+    /// it gets reloaded when `dependency` is hot reloaded.
+    ///
+    /// Returns false if the dependency would create a cycle, see `DependencyGraph::add_dependency`.
+    pub fn add_dependency<D, N>(&self, dependent: D, dependency: N) -> bool
+    where
+        D: Into<String>,
+        N: Into<String>,
+    {
+        self.dependencies
+            .write()
+            .add_dependency(dependent, dependency)
+    }
+
+    /// Returns the dependencies between the assets loaded so far, e.g. for debugging.
+    ///
+    /// The graph is locked until the returned value is dropped, which blocks loading assets.
+    pub fn dependency_graph<'a>(&'a self) -> impl Deref<Target = DependencyGraph> + 'a {
+        self.dependencies.read()
+    }
+
+    pub(crate) fn shared_dependency_graph(&self) -> Arc<RwLock<DependencyGraph>> {
+        self.dependencies.clone()
+    }
+
     fn source(&self, source: &str) -> Arc<Source> {
         self.sources
             .get(source)
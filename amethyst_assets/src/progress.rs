@@ -155,11 +155,16 @@ impl Tracker for ProgressCounterTracker {
     }
 }
 
+/// Describes an asset that failed to load, as reported by `ProgressCounter::errors`.
 #[derive(Debug)]
 pub struct AssetErrorMeta {
+    /// The error that occurred while loading the asset.
     pub error: Error,
+    /// The id of the handle of the asset.
     pub handle_id: u32,
+    /// The type name of the asset.
     pub asset_type_name: &'static str,
+    /// The name of the asset that was requested from the `Loader`.
     pub asset_name: String,
 }
 
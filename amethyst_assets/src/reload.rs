@@ -1,6 +1,17 @@
 //! Defines the `Reload` trait.
 
-use std::{sync::Arc, time::Instant};
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use fnv::FnvHashSet;
+use notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::RwLock;
 
 use amethyst_core as core;
 use amethyst_core::{
@@ -8,7 +19,7 @@ use amethyst_core::{
     SystemBundle, Time,
 };
 
-use {Asset, Format, FormatValue, Loader, Result, Source};
+use {Asset, DependencyGraph, Format, FormatValue, Loader, Result, Source};
 
 /// This bundle activates hot reload for the `Loader`,
 /// adds a `HotReloadStrategy` and the `HotReloadSystem`.
@@ -50,6 +61,7 @@ impl<'a, 'b> SystemBundle<'a, 'b> for HotReloadBundle {
 /// ```
 #[derive(Clone)]
 pub struct HotReloadStrategy {
+    dependencies: Option<Arc<RwLock<DependencyGraph>>>,
     inner: HotReloadStrategyInner,
 }
 
@@ -59,6 +71,7 @@ impl HotReloadStrategy {
         use std::u64::MAX;
 
         HotReloadStrategy {
+            dependencies: None,
             inner: HotReloadStrategyInner::Every {
                 interval: n,
                 last: Instant::now(),
@@ -72,6 +85,7 @@ impl HotReloadStrategy {
         use std::u64::MAX;
 
         HotReloadStrategy {
+            dependencies: None,
             inner: HotReloadStrategyInner::Trigger {
                 triggered: false,
                 frame_number: MAX,
@@ -79,9 +93,29 @@ impl HotReloadStrategy {
         }
     }
 
+    /// Watches the directories of the `Loader`'s sources for changes, and only reloads the
+    /// assets of changed files.
+    ///
+    /// Change notifications for a file are collected until there were none for `debounce`, so
+    /// that editors writing a file in several steps only cause a single reload. Sources which
+    /// don't read from directories on disk aren't hot reloaded with this strategy.
+    pub fn watch(debounce: Duration) -> Self {
+        use std::u64::MAX;
+
+        HotReloadStrategy {
+            dependencies: None,
+            inner: HotReloadStrategyInner::Watch {
+                debounce,
+                changed: Default::default(),
+                frame_number: MAX,
+            },
+        }
+    }
+
     /// Never do any hot-reloading.
     pub fn never() -> Self {
         HotReloadStrategy {
+            dependencies: None,
             inner: HotReloadStrategyInner::Never,
         }
     }
@@ -104,9 +138,25 @@ impl HotReloadStrategy {
         match self.inner {
             HotReloadStrategyInner::Every { frame_number, .. } => frame_number == current_frame,
             HotReloadStrategyInner::Trigger { frame_number, .. } => frame_number == current_frame,
+            HotReloadStrategyInner::Watch { frame_number, .. } => frame_number == current_frame,
             HotReloadStrategyInner::Never => false,
         }
     }
+
+    /// Crate-internal method returning the names of the changed files if only their assets need
+    /// to be checked for a reload, or `None` if all assets need to be checked.
+    pub(crate) fn changed(&self) -> Option<&FnvHashSet<String>> {
+        match self.inner {
+            HotReloadStrategyInner::Watch { ref changed, .. } => Some(changed),
+            _ => None,
+        }
+    }
+
+    /// Crate-internal method returning the dependency graph of the `Loader`, used to reload the
+    /// dependents of reloaded assets.
+    pub(crate) fn dependencies(&self) -> Option<&Arc<RwLock<DependencyGraph>>> {
+        self.dependencies.as_ref()
+    }
 }
 
 impl Default for HotReloadStrategy {
@@ -126,12 +176,86 @@ enum HotReloadStrategyInner {
         triggered: bool,
         frame_number: u64,
     },
+    Watch {
+        debounce: Duration,
+        changed: FnvHashSet<String>,
+        frame_number: u64,
+    },
     Never,
 }
 
+/// Watches directories and turns the changed paths into file names relative to them.
+struct DirectoryWatcher {
+    // Dropping the watcher stops watching.
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    roots: Vec<PathBuf>,
+}
+
+impl DirectoryWatcher {
+    fn new(debounce: Duration, directories: Vec<PathBuf>) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::watcher(sender, debounce)?;
+        let mut roots = Vec::with_capacity(directories.len());
+        for directory in directories {
+            // Notifications use the watched path, which may be relative or a symlink otherwise.
+            let root = directory.canonicalize().unwrap_or(directory);
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            roots.push(root);
+        }
+
+        Ok(DirectoryWatcher {
+            _watcher: watcher,
+            events,
+            roots,
+        })
+    }
+
+    /// Returns the names of the files changed since the last call.
+    fn changed(&self) -> FnvHashSet<String> {
+        let mut changed = FnvHashSet::default();
+        for event in self.events.try_iter() {
+            let paths = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Remove(path) => vec![path],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                DebouncedEvent::Error(e, path) => {
+                    warn!("Error while watching {:?} for hot reloading: {}", path, e);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            for path in paths {
+                let names = self
+                    .roots
+                    .iter()
+                    .filter_map(|root| path.strip_prefix(root).ok())
+                    .map(|relative| {
+                        relative
+                            .iter()
+                            .map(|component| component.to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/")
+                    });
+                changed.extend(names);
+            }
+        }
+
+        changed
+    }
+}
+
 /// System for updating `HotReloadStrategy`.
+///
+/// Watching directories and reloading the dependents of reloaded assets need the `Loader`
+/// resource, and are skipped without it.
 pub struct HotReloadSystem {
     initial_strategy: HotReloadStrategy,
+    watcher: Option<DirectoryWatcher>,
+    /// Set once creating the watcher failed, so that it isn't retried every frame.
+    watch_failed: bool,
 }
 
 impl HotReloadSystem {
@@ -139,14 +263,29 @@ impl HotReloadSystem {
     pub fn new(strategy: HotReloadStrategy) -> Self {
         HotReloadSystem {
             initial_strategy: strategy,
+            watcher: None,
+            watch_failed: false,
         }
     }
 }
 
 impl<'a> System<'a> for HotReloadSystem {
-    type SystemData = (Read<'a, Time>, Write<'a, HotReloadStrategy>);
+    type SystemData = (
+        Read<'a, Time>,
+        Write<'a, HotReloadStrategy>,
+        Option<Read<'a, Loader>>,
+    );
+
+    fn run(&mut self, (time, mut strategy, loader): Self::SystemData) {
+        if strategy.dependencies.is_none() {
+            strategy.dependencies = loader
+                .as_ref()
+                .map(|loader| loader.shared_dependency_graph());
+        }
+        if let Some(ref dependencies) = strategy.dependencies {
+            dependencies.write().expire_stale(time.frame_number());
+        }
 
-    fn run(&mut self, (time, mut strategy): Self::SystemData) {
         match strategy.inner {
             HotReloadStrategyInner::Trigger {
                 ref mut triggered,
@@ -167,6 +306,39 @@ impl<'a> System<'a> for HotReloadSystem {
                     *last = Instant::now();
                 }
             }
+            HotReloadStrategyInner::Watch {
+                debounce,
+                ref mut changed,
+                ref mut frame_number,
+            } => {
+                if self.watcher.is_none() && !self.watch_failed {
+                    if let Some(ref loader) = loader {
+                        match DirectoryWatcher::new(debounce, loader.directories()) {
+                            Ok(watcher) => self.watcher = Some(watcher),
+                            Err(e) => {
+                                error!(
+                                    "Failed to watch asset directories, assets won't be hot \
+                                     reloaded: {}",
+                                    e
+                                );
+                                self.watch_failed = true;
+                            }
+                        }
+                    }
+                }
+                if let Some(ref watcher) = self.watcher {
+                    let names = watcher.changed();
+                    if !names.is_empty() {
+                        // Storages which didn't process the current frame yet still need the
+                        // names of this frame.
+                        if *frame_number != time.frame_number() {
+                            changed.clear();
+                        }
+                        changed.extend(names);
+                        *frame_number = time.frame_number() + 1;
+                    }
+                }
+            }
             HotReloadStrategyInner::Never => {}
         }
     }
@@ -175,7 +347,9 @@ impl<'a> System<'a> for HotReloadSystem {
         use amethyst_core::specs::prelude::SystemData;
         Self::SystemData::setup(res);
         res.insert(self.initial_strategy.clone());
-        res.fetch_mut::<Loader>().set_hot_reload(true);
+        if let Some(mut loader) = res.try_fetch_mut::<Loader>() {
+            loader.set_hot_reload(true);
+        }
     }
 }
 
@@ -189,6 +363,13 @@ pub trait Reload<A: Asset>: ReloadClone<A> + Send + Sync + 'static {
     fn format(&self) -> &'static str;
     /// Reloads the asset.
     fn reload(self: Box<Self>) -> Result<FormatValue<A>>;
+    /// Returns the names of the assets the format declares as dependencies of the asset, which
+    /// are recorded again when it is reloaded.
+    ///
+    /// The default implementation declares no dependencies.
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 pub trait ReloadClone<A> {
@@ -290,4 +471,8 @@ where
 
         format.import(path, source, options, true)
     }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.format.dependencies(&self.path, &self.options)
+    }
 }
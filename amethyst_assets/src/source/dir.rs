@@ -51,6 +51,10 @@ impl Source for Directory {
             .map(|d| d.as_secs())
     }
 
+    fn directories(&self) -> Vec<PathBuf> {
+        vec![self.loc.clone()]
+    }
+
     fn load(&self, path: &str) -> Result<Vec<u8>> {
         #[cfg(feature = "profiler")]
         profile_scope!("dir_load_asset");
@@ -0,0 +1,149 @@
+use std::{path::PathBuf, u64};
+
+use fnv::FnvHashMap;
+use parking_lot::Mutex;
+
+use {source::Source, Error, ErrorKind, Result, ResultExt};
+
+/// Layered source, stacking several sources on top of each other.
+///
+/// Each path is resolved to the layer with the highest priority which has it, so that e.g. a mod
+/// directory can override the assets of the base game file by file. Layers added later have a
+/// higher priority.
+///
+/// The winning layer is resolved again every time a path is used. If a different layer wins than
+/// the one which served the last load of a path, e.g. because a file was added to or removed from
+/// a mod directory, `modified` reports the path as modified so that hot reloading picks up the
+/// file of the new layer.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let source = Layered::new()
+///     .with_layer("base", Directory::new("assets"))
+///     .with_layer("dlc", Pak::open("dlc.pak")?)
+///     .with_layer("mod", Directory::new("mods/foo"));
+/// loader.add_source("game", source);
+/// ```
+#[derive(Default)]
+pub struct Layered {
+    layers: Vec<(String, Box<Source>)>,
+    served: Mutex<FnvHashMap<String, usize>>,
+}
+
+impl Layered {
+    /// Creates a new layered source without any layers.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a layer on top of the existing ones, so that its files override theirs.
+    pub fn with_layer<I, S>(mut self, name: I, source: S) -> Self
+    where
+        I: Into<String>,
+        S: Source,
+    {
+        self.layers.push((name.into(), Box::new(source)));
+        self
+    }
+
+    /// Returns the names of the layers, from the lowest to the highest priority.
+    pub fn layers(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the name of the layer which currently serves the path, or `None` if no layer has
+    /// it.
+    pub fn layer_of(&self, path: &str) -> Option<&str> {
+        self.resolve(path)
+            .map(|index| self.layers[index].0.as_str())
+    }
+
+    /// Returns the index of the layer with the highest priority which has the path.
+    fn resolve(&self, path: &str) -> Option<usize> {
+        self.layers
+            .iter()
+            .rposition(|(_, source)| source.modified(path).is_ok())
+    }
+
+    fn resolve_or_err(&self, path: &str) -> Result<usize> {
+        self.resolve(path)
+            .ok_or_else(|| Error::from(format!("No layer has the path {:?}", path)))
+            .chain_err(|| ErrorKind::Source)
+    }
+}
+
+impl Source for Layered {
+    fn modified(&self, path: &str) -> Result<u64> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("layered_modified_asset");
+
+        let index = self.resolve_or_err(path)?;
+        match self.served.lock().get(path) {
+            Some(&served) if served != index => Ok(u64::MAX),
+            _ => self.layers[index].1.modified(path),
+        }
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>> {
+        self.load_with_metadata(path).map(|(bytes, _)| bytes)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64)> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("layered_load_asset_with_metadata");
+
+        let index = self.resolve_or_err(path)?;
+        let (ref name, ref source) = self.layers[index];
+        let loaded = source
+            .load_with_metadata(path)
+            .chain_err(|| format!("Failed to load {:?} from layer {:?}", path, name))?;
+        self.served.lock().insert(path.to_owned(), index);
+
+        Ok(loaded)
+    }
+
+    fn directories(&self) -> Vec<PathBuf> {
+        self.layers
+            .iter()
+            .flat_map(|(_, source)| source.directories())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, path::Path, process, u64};
+
+    use source::{Directory, Source};
+
+    use super::Layered;
+
+    #[test]
+    fn upper_layer_overrides_and_triggers_reload() {
+        let test_assets_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+        let mod_dir =
+            env::temp_dir().join(format!("amethyst_assets_test_layered_{}", process::id()));
+        let _ = fs::remove_dir_all(&mod_dir);
+        fs::create_dir_all(mod_dir.join("subdir")).expect("Failed to create mod directory");
+
+        let layered = Layered::new()
+            .with_layer("base", Directory::new(test_assets_dir))
+            .with_layer("mod", Directory::new(&mod_dir));
+
+        assert_eq!(Some("base"), layered.layer_of("subdir/asset"));
+        assert_eq!(b"data".to_vec(), layered.load("subdir/asset").unwrap());
+        assert!(layered.modified("subdir/asset").unwrap() < u64::MAX);
+
+        fs::write(mod_dir.join("subdir/asset"), "mod data").expect("Failed to write mod asset");
+        assert_eq!(Some("mod"), layered.layer_of("subdir/asset"));
+        assert_eq!(u64::MAX, layered.modified("subdir/asset").unwrap());
+        assert_eq!(b"mod data".to_vec(), layered.load("subdir/asset").unwrap());
+        assert!(layered.modified("subdir/asset").unwrap() < u64::MAX);
+
+        assert_eq!(None, layered.layer_of("missing"));
+        assert!(layered.load("missing").is_err());
+
+        fs::remove_dir_all(mod_dir).expect("Failed to remove mod directory");
+    }
+}
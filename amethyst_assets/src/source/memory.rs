@@ -0,0 +1,141 @@
+use std::{
+    cmp,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use fnv::FnvHashMap;
+use parking_lot::RwLock;
+
+use {source::Source, Error, ErrorKind, Result, ResultExt};
+
+#[derive(Debug, Default)]
+struct Files {
+    files: FnvHashMap<String, (Vec<u8>, u64)>,
+    last_modified: u64,
+}
+
+/// In-memory source, for tests and generated assets.
+///
+/// Files can be inserted, replaced and removed at any time, also after the source was added to
+/// the `Loader`. Clones of a `MemorySource` share their files, so keep a clone around to edit
+/// them.
+///
+/// Every write bumps the modification time of the file, so that hot reloading picks up the new
+/// contents. Modification times are seconds since `UNIX_EPOCH`, but increase with every write
+/// even if several happen in the same second.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let source = MemorySource::new();
+/// source.insert("level.ron", "(size: (20, 20))");
+/// loader.add_source("generated", source.clone());
+///
+/// // Later on, the level asset gets reloaded with the new data.
+/// source.insert("level.ron", "(size: (40, 40))");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    files: Arc<RwLock<Files>>,
+}
+
+impl MemorySource {
+    /// Creates a new memory source without any files.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Inserts a file, or replaces its contents if it already exists.
+    ///
+    /// Returns the previous contents of the file.
+    pub fn insert<P, B>(&self, path: P, bytes: B) -> Option<Vec<u8>>
+    where
+        P: Into<String>,
+        B: Into<Vec<u8>>,
+    {
+        let mut files = self.files.write();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let modified = cmp::max(now, files.last_modified + 1);
+        files.last_modified = modified;
+
+        files
+            .files
+            .insert(path.into(), (bytes.into(), modified))
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Removes a file, returning its contents.
+    pub fn remove(&self, path: &str) -> Option<Vec<u8>> {
+        self.files
+            .write()
+            .files
+            .remove(path)
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Returns true if there is a file at the path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.read().files.contains_key(path)
+    }
+
+    fn missing(path: &str) -> Error {
+        Error::from(format!("No file {:?} in memory source", path))
+    }
+}
+
+impl Source for MemorySource {
+    fn modified(&self, path: &str) -> Result<u64> {
+        self.files
+            .read()
+            .files
+            .get(path)
+            .map(|&(_, modified)| modified)
+            .ok_or_else(|| Self::missing(path))
+            .chain_err(|| ErrorKind::Source)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>> {
+        self.load_with_metadata(path).map(|(bytes, _)| bytes)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64)> {
+        self.files
+            .read()
+            .files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Self::missing(path))
+            .chain_err(|| ErrorKind::Source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use source::Source;
+
+    use super::MemorySource;
+
+    #[test]
+    fn writes_bump_modification_time() {
+        let source = MemorySource::new();
+        let shared = source.clone();
+        assert!(source.load("asset").is_err());
+
+        assert_eq!(None, source.insert("asset", "data"));
+        let (bytes, modified) = shared.load_with_metadata("asset").unwrap();
+        assert_eq!(b"data".to_vec(), bytes);
+        assert!(modified > 0);
+
+        assert_eq!(Some(b"data".to_vec()), source.insert("asset", "new data"));
+        assert_eq!(b"new data".to_vec(), shared.load("asset").unwrap());
+        assert!(shared.modified("asset").unwrap() > modified);
+
+        assert_eq!(Some(b"new data".to_vec()), source.remove("asset"));
+        assert!(!shared.contains("asset"));
+        assert!(shared.modified("asset").is_err());
+    }
+}
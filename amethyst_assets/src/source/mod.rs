@@ -1,8 +1,18 @@
+use std::path::PathBuf;
+
 use Result;
 
-pub use self::dir::Directory;
+pub use self::{
+    dir::Directory,
+    layered::Layered,
+    memory::MemorySource,
+    pak::{build_pak, Pak},
+};
 
 mod dir;
+mod layered;
+mod memory;
+mod pak;
 
 /// A trait for asset sources, which provides
 /// methods for loading bytes.
@@ -29,4 +39,13 @@ pub trait Source: Send + Sync + 'static {
 
         Ok((b, m))
     }
+
+    /// Returns the directories on disk this source reads its files from, with the files named
+    /// after their path relative to one of them.
+    ///
+    /// This is used to watch for changed files when hot reloading. The default implementation
+    /// returns no directories, for sources which don't read from the file system.
+    fn directories(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,397 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use fnv::FnvHashMap;
+use parking_lot::Mutex;
+
+use {source::Source, Error, ErrorKind, Result, ResultExt};
+
+const MAGIC: &[u8; 4] = b"APAK";
+const VERSION: u32 = 1;
+/// Magic, version and index offset.
+const HEADER_LEN: u64 = 16;
+
+/// An entry of the pak index.
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    offset: u64,
+    len: u64,
+    compressed: bool,
+    modified: u64,
+}
+
+/// Pak archive source, reading all assets from a single file.
+///
+/// The archive starts with a header, followed by the data of the entries and an index mapping
+/// each path to its data. The index is read once when opening the archive, so looking up an
+/// entry doesn't touch the file system. Entries can be stored deflate compressed, and keep the
+/// modification time their file had when the archive was built.
+///
+/// Archives are created from an assets directory with `build_pak`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let pak = Pak::open("assets.pak")?;
+/// let game = Application::build("assets/", LoadingState)?
+///     .with_source("pak", pak)
+///     .build(game_data)?;
+/// ```
+#[derive(Debug)]
+pub struct Pak {
+    path: PathBuf,
+    file: Mutex<File>,
+    index: FnvHashMap<String, Entry>,
+}
+
+impl Pak {
+    /// Opens a pak archive and reads its index.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let file = File::open(&path)
+            .chain_err(|| format!("Failed to open archive {:?}", path))
+            .chain_err(|| ErrorKind::Source)?;
+        let index = file
+            .metadata()
+            .map_err(Error::from)
+            .and_then(|metadata| read_index(&mut BufReader::new(&file), metadata.len()))
+            .chain_err(|| format!("Failed to read index of archive {:?}", path))
+            .chain_err(|| ErrorKind::Source)?;
+
+        Ok(Pak {
+            path,
+            file: Mutex::new(file),
+            index,
+        })
+    }
+
+    /// Returns true if the archive has an entry for the path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(path)
+    }
+
+    /// Returns the paths of all the entries in the archive.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(String::as_str)
+    }
+
+    fn entry(&self, path: &str) -> Result<Entry> {
+        self.index
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Error::from(format!("No entry {:?} in archive {:?}", path, self.path)))
+            .chain_err(|| ErrorKind::Source)
+    }
+}
+
+impl Source for Pak {
+    fn modified(&self, path: &str) -> Result<u64> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("pak_modified_asset");
+
+        self.entry(path).map(|entry| entry.modified)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("pak_load_asset");
+
+        let entry = self.entry(path)?;
+
+        let stored = {
+            let mut file = self.file.lock();
+            read_entry(&mut *file, &entry)
+                .chain_err(|| format!("Failed to read {:?} from archive {:?}", path, self.path))
+                .chain_err(|| ErrorKind::Source)?
+        };
+
+        if !entry.compressed {
+            return Ok(stored);
+        }
+
+        let mut v = Vec::new();
+        DeflateDecoder::new(&stored[..])
+            .read_to_end(&mut v)
+            .chain_err(|| {
+                format!(
+                    "Failed to decompress {:?} from archive {:?}",
+                    path, self.path
+                )
+            })
+            .chain_err(|| ErrorKind::Source)?;
+
+        Ok(v)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64)> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("pak_load_asset_with_metadata");
+
+        let modified = self.modified(path)?;
+        self.load(path).map(|bytes| (bytes, modified))
+    }
+}
+
+/// Builds a pak archive containing all the files of an assets directory, which can then be
+/// opened with `Pak::open`.
+///
+/// Entries are named after their path relative to `dir`, with `/` as separator. If `compress` is
+/// true, each entry is deflate compressed, unless that doesn't make it smaller.
+pub fn build_pak<P, Q>(dir: P, output: Q, compress: bool) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    let output = output.as_ref();
+
+    let mut files = Vec::new();
+    collect_files(dir, String::new(), &mut files)
+        .chain_err(|| format!("Failed to list the files of {:?}", dir))?;
+    files.sort();
+
+    write_pak(dir, output, &files, compress)
+        .chain_err(|| format!("Failed to write archive {:?}", output))
+}
+
+fn collect_files(dir: &Path, prefix: String, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).chain_err(|| format!("Failed to read directory {:?}", dir))? {
+        let entry = entry.chain_err(|| format!("Failed to read directory {:?}", dir))?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| Error::from(format!("File name {:?} is not valid UTF-8", name)))?;
+        let name = prefix.clone() + &name;
+
+        if entry.path().is_dir() {
+            collect_files(&entry.path(), name + "/", files)?;
+        } else {
+            files.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+fn write_pak(dir: &Path, output: &Path, files: &[String], compress: bool) -> Result<()> {
+    let mut out = BufWriter::new(File::create(output)?);
+    out.write_all(MAGIC)?;
+    write_u32(&mut out, VERSION)?;
+    // Patched with the index offset once the data is written.
+    write_u64(&mut out, 0)?;
+
+    let mut index = Vec::with_capacity(files.len());
+    let mut offset = HEADER_LEN;
+    for name in files {
+        let path = dir.join(name);
+        let mut data = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .chain_err(|| format!("Failed to read file {:?}", path))?;
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .chain_err(|| format!("Failed to fetch metadata for {:?}", path))?
+            .duration_since(UNIX_EPOCH)
+            .chain_err(|| "Anomalies with the system clock caused `duration_since` to fail")?
+            .as_secs();
+
+        let mut compressed = false;
+        if compress {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data)?;
+            let deflated = encoder.finish()?;
+            if deflated.len() < data.len() {
+                data = deflated;
+                compressed = true;
+            }
+        }
+
+        out.write_all(&data)?;
+        index.push((
+            name,
+            Entry {
+                offset,
+                len: data.len() as u64,
+                compressed,
+                modified,
+            },
+        ));
+        offset += data.len() as u64;
+    }
+
+    write_u32(&mut out, index.len() as u32)?;
+    for (name, entry) in index {
+        write_u32(&mut out, name.len() as u32)?;
+        out.write_all(name.as_bytes())?;
+        write_u64(&mut out, entry.offset)?;
+        write_u64(&mut out, entry.len)?;
+        out.write_all(&[entry.compressed as u8])?;
+        write_u64(&mut out, entry.modified)?;
+    }
+
+    out.seek(SeekFrom::Start(HEADER_LEN - 8))?;
+    write_u64(&mut out, offset)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Reads the data of an entry, checking that it still fits in the archive.
+fn read_entry(file: &mut File, entry: &Entry) -> Result<Vec<u8>> {
+    let file_len = file.metadata()?.len();
+    if entry.offset + entry.len > file_len {
+        bail!("Entry doesn't fit in the archive, which may have been truncated");
+    }
+    let mut stored = vec![0; entry.len as usize];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut stored)?;
+    Ok(stored)
+}
+
+/// Reads the index of an archive of `file_len` bytes, rejecting entries which don't fit in it.
+fn read_index<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<FnvHashMap<String, Entry>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("Not a pak archive");
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        bail!("Unsupported pak version {}", version);
+    }
+    let index_offset = read_u64(reader)?;
+    if index_offset < HEADER_LEN || index_offset > file_len {
+        bail!("Index offset {} is outside of the archive", index_offset);
+    }
+    reader.seek(SeekFrom::Start(index_offset))?;
+
+    let count = read_u32(reader)?;
+    let mut index = FnvHashMap::default();
+    for _ in 0..count {
+        let name_len = u64::from(read_u32(reader)?);
+        if name_len > file_len - index_offset {
+            bail!(
+                "Entry name of {} bytes doesn't fit in the archive",
+                name_len
+            );
+        }
+        let mut name = vec![0; name_len as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name)?;
+        let offset = read_u64(reader)?;
+        let len = read_u64(reader)?;
+        let mut compressed = [0];
+        reader.read_exact(&mut compressed)?;
+        let modified = read_u64(reader)?;
+        // Entry data lies between the header and the index.
+        if offset < HEADER_LEN
+            || offset
+                .checked_add(len)
+                .map_or(true, |end| end > index_offset)
+        {
+            bail!("Entry {:?} doesn't fit in the archive", name);
+        }
+
+        index.insert(
+            name,
+            Entry {
+                offset,
+                len,
+                compressed: compressed[0] != 0,
+                modified,
+            },
+        );
+    }
+
+    Ok(index)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    let mut bytes = [0; 4];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+    writer.write_all(&bytes).map_err(Into::into)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<()> {
+    write_u32(writer, value as u32)?;
+    write_u32(writer, (value >> 32) as u32)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes
+        .iter()
+        .enumerate()
+        .fold(0, |value, (i, &byte)| value | u32::from(byte) << (8 * i)))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let low = read_u32(reader)?;
+    let high = read_u32(reader)?;
+    Ok(u64::from(low) | u64::from(high) << 32)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, path::Path, process};
+
+    use source::Source;
+
+    use super::{build_pak, Pak};
+
+    #[test]
+    fn loads_asset_from_built_archive() {
+        let test_assets_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+
+        for &compress in &[false, true] {
+            let output = env::temp_dir().join(format!(
+                "amethyst_assets_test_{}_{}.pak",
+                process::id(),
+                compress
+            ));
+            build_pak(&test_assets_dir, &output, compress).expect("Failed to build archive");
+            let pak = Pak::open(&output).expect("Failed to open archive");
+
+            assert!(pak.contains("subdir/asset"));
+            assert_eq!(
+                "data".as_bytes().to_vec(),
+                pak.load("subdir/asset")
+                    .expect("Failed to load subdir/asset from archive")
+            );
+            assert!(pak.modified("subdir/asset").unwrap() > 0);
+            assert!(pak.load("missing").is_err());
+
+            fs::remove_file(output).expect("Failed to remove archive");
+        }
+    }
+
+    #[test]
+    fn rejects_entries_outside_of_archive() {
+        let output = env::temp_dir().join(format!(
+            "amethyst_assets_test_{}_corrupt.pak",
+            process::id()
+        ));
+        // Header followed by an index with one entry of 4 GB.
+        let mut bytes = b"APAK".to_vec();
+        bytes.extend_from_slice(&[1, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, b'a']);
+        bytes.extend_from_slice(&[16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        fs::write(&output, bytes).expect("Failed to write archive");
+
+        let opened = Pak::open(&output);
+        fs::remove_file(output).expect("Failed to remove archive");
+        assert!(opened.is_err());
+    }
+}
@@ -7,6 +7,7 @@ use std::{
 };
 
 use crossbeam::queue::MsQueue;
+use fnv::FnvHashSet;
 use hibitset::BitSet;
 use rayon::ThreadPool;
 
@@ -20,6 +21,7 @@ use amethyst_core::{
 
 use {
     asset::{Asset, FormatValue},
+    dependency,
     error::{Error, ErrorKind, Result, ResultExt},
     progress::Tracker,
     reload::{HotReloadStrategy, Reload},
@@ -174,7 +176,9 @@ impl<A: Asset> AssetStorage<A> {
                     } => {
                         let (asset, reload_obj) = match data
                             .map(|FormatValue { data, reload }| (data, reload))
-                            .and_then(|(d, rel)| f(d).map(|a| (a, rel)))
+                            .and_then(|(d, rel)| {
+                                dependency::processing(&name, || f(d)).map(|a| (a, rel))
+                            })
                             .chain_err(|| ErrorKind::Asset(name.clone()))
                         {
                             Ok((ProcessingState::Loaded(x), r)) => {
@@ -253,7 +257,9 @@ impl<A: Asset> AssetStorage<A> {
                     } => {
                         let (asset, reload_obj) = match data
                             .map(|FormatValue { data, reload }| (data, reload))
-                            .and_then(|(d, rel)| f(d).map(|a| (a, rel)))
+                            .and_then(|(d, rel)| {
+                                dependency::processing(&name, || f(d)).map(|a| (a, rel))
+                            })
                             .chain_err(|| ErrorKind::Asset(name.clone()))
                         {
                             Ok((ProcessingState::Loaded(x), r)) => (x, r),
@@ -298,6 +304,13 @@ impl<A: Asset> AssetStorage<A> {
                             let old = assets.get_mut(id);
                             *old = asset;
                         }
+                        if let Some(dependencies) =
+                            strategy.and_then(HotReloadStrategy::dependencies)
+                        {
+                            dependencies
+                                .write()
+                                .mark_dependents_stale(&name, frame_number);
+                        }
 
                         (reload_obj, handle)
                     }
@@ -339,22 +352,48 @@ impl<A: Asset> AssetStorage<A> {
             debug!("{:?}: Freed {} handle ids", A::NAME, count,);
         }
 
-        if strategy
+        let check_modified = strategy
             .map(|s| s.needs_reload(frame_number))
-            .unwrap_or(false)
-        {
+            .unwrap_or(false);
+        let stale_dependents = strategy
+            .and_then(HotReloadStrategy::dependencies)
+            .map_or(false, |dependencies| dependencies.read().has_stale());
+        if check_modified || stale_dependents {
             trace!("{:?}: Testing for asset reloads..", A::NAME);
-            self.hot_reload(pool);
+            self.hot_reload(pool, strategy, check_modified);
         }
     }
 
-    fn hot_reload(&mut self, pool: &ThreadPool) {
+    /// Reloads the assets whose dependencies were reloaded, and also the modified assets if
+    /// `check_modified` is true.
+    fn hot_reload(
+        &mut self,
+        pool: &ThreadPool,
+        strategy: Option<&HotReloadStrategy>,
+        check_modified: bool,
+    ) {
+        let changed = strategy.and_then(HotReloadStrategy::changed);
+        let dependencies = strategy.and_then(HotReloadStrategy::dependencies);
+
         self.reloads.retain(|&(ref handle, _)| !handle.is_dead());
-        while let Some(p) = self
-            .reloads
-            .iter()
-            .position(|&(_, ref rel)| rel.needs_reload())
-        {
+        // Only hold the lock of the graph while collecting the stale assets.
+        let mut stale = FnvHashSet::default();
+        if let Some(dependencies) = dependencies {
+            let mut dependencies = dependencies.write();
+            stale.extend(
+                self.reloads
+                    .iter()
+                    .map(|&(_, ref rel)| rel.name())
+                    .filter(|name| dependencies.take_stale(name)),
+            );
+        }
+        while let Some(p) = self.reloads.iter().position(|&(_, ref rel)| {
+            let name = rel.name();
+            stale.contains(&name)
+                || (check_modified
+                    && changed.map_or(true, |changed| changed.contains(&name))
+                    && rel.needs_reload())
+        }) {
             let (handle, rel): (WeakHandle<_>, Box<Reload<_>>) = self.reloads.swap_remove(p);
 
             let name = rel.name();
@@ -371,10 +410,21 @@ impl<A: Asset> AssetStorage<A> {
 
             if let Some(handle) = handle {
                 let processed = self.processed.clone();
+                let dependencies = dependencies.cloned();
                 pool.spawn(move || {
                     let old_reload = rel.clone();
+                    let declared = rel.dependencies();
                     let data = rel.reload().chain_err(|| ErrorKind::Format(format));
 
+                    if let Some(dependencies) = dependencies.filter(|_| data.is_ok()) {
+                        // The assets loaded while processing the new data are recorded again.
+                        let mut dependencies = dependencies.write();
+                        dependencies.remove_dependencies(&name);
+                        for dependency in declared {
+                            dependencies.add_dependency(name.clone(), dependency);
+                        }
+                    }
+
                     let p = Processed::HotReload {
                         data,
                         name,
@@ -0,0 +1,155 @@
+//! Events about entities being created and deleted.
+
+use std::{borrow::Cow, mem};
+
+use hibitset::{BitSet, BitSetAnd, BitSetLike, BitSetNot};
+use shrev::EventChannel;
+use specs::prelude::{Entities, Entity, Join, ReadStorage, System, Write};
+
+use named::Named;
+
+/// An event sent by the `EntityEventSystem` when an entity was created or deleted.
+///
+/// This allows invalidating caches of entities, e.g. the result of a lookup with a `TagFinder`
+/// or a `UiFinder`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityEvent {
+    /// The entity was created.
+    Created {
+        /// The created entity.
+        entity: Entity,
+        /// The `Named` name of the entity when it was first seen alive.
+        name: Option<Cow<'static, str>>,
+    },
+    /// The entity was deleted.
+    Deleted {
+        /// The deleted entity, which is not alive anymore.
+        entity: Entity,
+        /// The last known `Named` name of the entity.
+        name: Option<Cow<'static, str>>,
+    },
+}
+
+/// Publishes an `EntityEvent` to the `EventChannel<EntityEvent>` resource for every entity created
+/// or deleted since the previous run.
+///
+/// Specs has no hook for entity creation, so entities are compared with the ones alive during the
+/// previous run. Events are sent once the world has been maintained, and a short-lived entity,
+/// created and deleted between two runs, is not reported at all: caches must not expect a
+/// `Deleted` event for every entity they looked up, only for the ones reported as `Created`.
+///
+/// The system is opt-in, as it goes through all alive entities every run. Entities are looked up
+/// by index and deleted ones are found with bit set operations, so this only costs a few
+/// comparisons per entity.
+#[derive(Default)]
+pub struct EntityEventSystem {
+    /// The entities alive during the previous run and their name, by entity id.
+    known: Vec<Option<(Entity, Option<Cow<'static, str>>)>>,
+    /// The ids of the entities alive during the previous run.
+    alive: BitSet,
+    /// The ids of the entities alive during the current run.
+    current: BitSet,
+}
+
+impl EntityEventSystem {
+    /// Creates a new `EntityEventSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for EntityEventSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Named>,
+        Write<'a, EventChannel<EntityEvent>>,
+    );
+
+    fn run(&mut self, (entities, names, mut events): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("entity_event_system");
+
+        self.current.clear();
+        let mut created = Vec::new();
+        for entity in (&*entities).join() {
+            self.current.add(entity.id());
+            let index = entity.id() as usize;
+            if index >= self.known.len() {
+                self.known.resize(index + 1, None);
+            }
+
+            let name = names.get(entity).map(|named| &named.name);
+            if let Some((known, ref mut known_name)) = self.known[index] {
+                if known == entity {
+                    if known_name.as_ref() != name {
+                        *known_name = name.cloned();
+                    }
+                    continue;
+                }
+            }
+            let name = name.cloned();
+            // The index was reused since the previous run, so the previous entity was deleted.
+            if let Some((deleted, name)) = self.known[index].take() {
+                events.single_write(EntityEvent::Deleted {
+                    entity: deleted,
+                    name,
+                });
+            }
+            self.known[index] = Some((entity, name.clone()));
+            created.push(EntityEvent::Created { entity, name });
+        }
+
+        for id in BitSetAnd(&self.alive, BitSetNot(&self.current)).iter() {
+            if let Some((entity, name)) = self.known[id as usize].take() {
+                events.single_write(EntityEvent::Deleted { entity, name });
+            }
+        }
+        mem::swap(&mut self.alive, &mut self.current);
+
+        events.iter_write(created);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shred::RunNow;
+    use specs::prelude::{Builder, World};
+
+    use super::*;
+    use named::WithNamed;
+
+    #[test]
+    fn created_and_deleted_entities_are_reported() {
+        let mut world = World::new();
+        let mut system = EntityEventSystem::new();
+        system.setup(&mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<EntityEvent>>()
+            .register_reader();
+
+        let entity = world.create_entity().named("player").build();
+        system.run_now(&world.res);
+        world.delete_entity(entity).unwrap();
+        world.maintain();
+        system.run_now(&world.res);
+
+        let events = world
+            .read_resource::<EventChannel<EntityEvent>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                EntityEvent::Created {
+                    entity,
+                    name: Some("player".into()),
+                },
+                EntityEvent::Deleted {
+                    entity,
+                    name: Some("player".into()),
+                },
+            ],
+            events
+        );
+    }
+}
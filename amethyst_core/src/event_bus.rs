@@ -0,0 +1,303 @@
+//! An event bus resource delivering events of any type to prioritised listeners.
+
+use std::{
+    any::{Any, TypeId},
+    mem,
+};
+
+use fnv::FnvHashMap;
+use shrev::EventChannel;
+use specs::Resources;
+
+/// What an event bus listener wants to happen with the event it received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    /// Pass the event on to the listeners with a lower priority.
+    Continue,
+    /// Stop the event, listeners with a lower priority and event readers won't receive it.
+    Consume,
+}
+
+/// Identifies a listener subscribed to an `EventBus`, so that it can be unsubscribed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId {
+    type_id: TypeId,
+    id: u64,
+}
+
+type Listener<E> = Box<FnMut(&E) -> Propagation + Send + Sync>;
+
+/// The listeners and pending events of a single event type.
+struct Topic<E> {
+    listeners: Vec<(i32, u64, Listener<E>)>,
+    /// Events to deliver, with whether the listeners were already notified.
+    queued: Vec<(E, bool)>,
+}
+
+impl<E> Default for Topic<E> {
+    fn default() -> Self {
+        Topic {
+            listeners: Vec::new(),
+            queued: Vec::new(),
+        }
+    }
+}
+
+impl<E> Topic<E> {
+    /// Calls the listeners in priority order, and returns true if one of them consumed the event.
+    fn notify(&mut self, event: &E) -> bool {
+        self.listeners
+            .iter_mut()
+            .any(|(_, _, listener)| listener(event) == Propagation::Consume)
+    }
+}
+
+/// Type erased `Topic`, so that topics of all event types can be stored in the same map.
+trait AnyTopic: Any + Send + Sync {
+    fn deliver(&mut self, res: &Resources);
+
+    fn unsubscribe(&mut self, id: u64) -> bool;
+
+    fn as_any_mut(&mut self) -> &mut Any;
+}
+
+impl<E> AnyTopic for Topic<E>
+where
+    E: Send + Sync + 'static,
+{
+    fn deliver(&mut self, res: &Resources) {
+        let mut unconsumed = Vec::new();
+        for (event, notified) in mem::replace(&mut self.queued, Vec::new()) {
+            if notified || !self.notify(&event) {
+                unconsumed.push(event);
+            }
+        }
+        if let Some(mut channel) = res.try_fetch_mut::<EventChannel<E>>() {
+            channel.iter_write(unconsumed);
+        }
+    }
+
+    fn unsubscribe(&mut self, id: u64) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|&(_, listener, _)| listener != id);
+        self.listeners.len() != len
+    }
+
+    fn as_any_mut(&mut self) -> &mut Any {
+        self
+    }
+}
+
+/// Event bus resource, delivering events of any type to the listeners subscribed to that type.
+///
+/// Listeners are called from the highest to the lowest priority, and listeners with the same
+/// priority in the order they subscribed. A listener can consume an event, which stops it from
+/// reaching the following listeners.
+///
+/// Events that no listener consumed are then written to the `EventChannel` of their type, if there
+/// is one in the world. This way events sent through the bus are also read by the `EventReader`s
+/// of that channel, including the ones generated with `#[derive(EventReader)]` for aggregates like
+/// `StateEvent`, without the bus having to know about them.
+///
+/// The application calls `EventBus::dispatch` every frame, after the callback queue and timers
+/// and before states handle their events.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut bus = world.write_resource::<EventBus>();
+/// bus.subscribe(10, |hit: &PlayerHit| {
+///     if hit.damage == 0 {
+///         Propagation::Consume
+///     } else {
+///         Propagation::Continue
+///     }
+/// });
+/// bus.publish(PlayerHit { damage: 3 });
+/// ```
+#[derive(Default)]
+pub struct EventBus {
+    topics: FnvHashMap<TypeId, Box<AnyTopic>>,
+    next_id: u64,
+}
+
+impl EventBus {
+    /// Creates a new, empty event bus.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Subscribes a listener to the events of type `E`, with the given priority.
+    ///
+    /// # Returns
+    ///
+    /// The id to unsubscribe the listener with.
+    pub fn subscribe<E, F>(&mut self, priority: i32, listener: F) -> ListenerId
+    where
+        E: Send + Sync + 'static,
+        F: FnMut(&E) -> Propagation + Send + Sync + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let listeners = &mut self.topic::<E>().listeners;
+        // Listeners are sorted by descending priority, insert after the ones with the same
+        // priority.
+        let index = listeners
+            .iter()
+            .position(|&(other, _, _)| other < priority)
+            .unwrap_or_else(|| listeners.len());
+        listeners.insert(index, (priority, id, Box::new(listener)));
+        ListenerId {
+            type_id: TypeId::of::<E>(),
+            id,
+        }
+    }
+
+    /// Unsubscribes a listener.
+    ///
+    /// # Returns
+    ///
+    /// False if the listener was already unsubscribed.
+    pub fn unsubscribe(&mut self, listener: ListenerId) -> bool {
+        self.topics
+            .get_mut(&listener.type_id)
+            .map_or(false, |topic| topic.unsubscribe(listener.id))
+    }
+
+    /// Queues an event, which will be delivered to the listeners on the next `dispatch`.
+    pub fn publish<E>(&mut self, event: E)
+    where
+        E: Send + Sync + 'static,
+    {
+        self.topic::<E>().queued.push((event, false));
+    }
+
+    /// Delivers an event to the listeners immediately.
+    ///
+    /// If no listener consumed the event, it is written to the `EventChannel` of its type on the
+    /// next `dispatch`, after the events queued before it.
+    ///
+    /// # Returns
+    ///
+    /// True if a listener consumed the event.
+    pub fn publish_now<E>(&mut self, event: E) -> bool
+    where
+        E: Send + Sync + 'static,
+    {
+        let topic = self.topic::<E>();
+        let consumed = topic.notify(&event);
+        if !consumed {
+            topic.queued.push((event, true));
+        }
+        consumed
+    }
+
+    /// Delivers the queued events of the `EventBus` resource to its listeners, and writes the
+    /// events which weren't consumed to their `EventChannel`s.
+    ///
+    /// Does nothing if there is no `EventBus` resource.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn dispatch(res: &Resources) {
+        if let Some(mut bus) = res.try_fetch_mut::<EventBus>() {
+            for topic in bus.topics.values_mut() {
+                topic.deliver(res);
+            }
+        }
+    }
+
+    fn topic<E>(&mut self) -> &mut Topic<E>
+    where
+        E: Send + Sync + 'static,
+    {
+        self.topics
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Topic::<E>::default()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("Event bus topic stored under the wrong type id")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Hit(u32);
+
+    fn recorder(
+        name: &'static str,
+        log: &Arc<Mutex<Vec<(&'static str, u32)>>>,
+        propagation: Propagation,
+    ) -> impl FnMut(&Hit) -> Propagation + Send + Sync + 'static {
+        let log = log.clone();
+        move |hit: &Hit| {
+            log.lock().unwrap().push((name, hit.0));
+            propagation
+        }
+    }
+
+    #[test]
+    fn listeners_by_priority_and_consumption() {
+        let mut res = Resources::new();
+        res.insert(EventBus::new());
+        res.insert(EventChannel::<Hit>::new());
+        let mut reader = res.fetch_mut::<EventChannel<Hit>>().register_reader();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let high = {
+            let mut bus = res.fetch_mut::<EventBus>();
+            bus.subscribe(0, recorder("low", &log, Propagation::Continue));
+            let high = bus.subscribe(5, recorder("high", &log, Propagation::Continue));
+            bus.subscribe(0, recorder("last", &log, Propagation::Consume));
+
+            bus.publish(Hit(1));
+            assert!(log.lock().unwrap().is_empty());
+            high
+        };
+        EventBus::dispatch(&res);
+        assert_eq!(
+            vec![("high", 1), ("low", 1), ("last", 1)],
+            *log.lock().unwrap()
+        );
+        assert_eq!(
+            0,
+            res.fetch::<EventChannel<Hit>>().read(&mut reader).count()
+        );
+
+        let mut bus = res.fetch_mut::<EventBus>();
+        assert!(bus.unsubscribe(high));
+        assert!(!bus.unsubscribe(high));
+        assert!(bus.publish_now(Hit(2)));
+        assert_eq!(("low", 2), log.lock().unwrap()[3]);
+    }
+
+    #[test]
+    fn unconsumed_events_reach_the_channel() {
+        let mut res = Resources::new();
+        res.insert(EventBus::new());
+        res.insert(EventChannel::<Hit>::new());
+        let mut reader = res.fetch_mut::<EventChannel<Hit>>().register_reader();
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let mut bus = res.fetch_mut::<EventBus>();
+            bus.subscribe(0, recorder("listener", &log, Propagation::Continue));
+            bus.publish(Hit(1));
+            assert!(!bus.publish_now(Hit(2)));
+            assert_eq!(vec![("listener", 2)], *log.lock().unwrap());
+        }
+        EventBus::dispatch(&res);
+        assert_eq!(
+            vec![Hit(1), Hit(2)],
+            res.fetch::<EventChannel<Hit>>()
+                .read(&mut reader)
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+}
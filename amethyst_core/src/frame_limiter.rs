@@ -39,7 +39,7 @@
 //!
 //! # Frame Rate Limiting Strategies
 //!
-//! The four possible strategies described by [`FrameRateLimitStrategy`] are as follows:
+//! The five possible strategies described by [`FrameRateLimitStrategy`] are as follows:
 //!
 //! * `Unlimited` will not try to limit the frame rate to the specified maximum. Amethyst
 //!   will call [`thread::yield_now`] once and then continue to the next frame.
@@ -54,6 +54,8 @@
 //!   and then will yield until the next frame starts. This approach attempts to get the
 //!   consistent frame timings of yielding, while reducing CPU usage compared to the yield-only
 //!   approach.
+//! * `Adaptive` works like `SleepAndYield`, but measures how late the operating system wakes the
+//!   game after sleeping, and adjusts the time left for yielding accordingly.
 //!
 //! By default amethyst will use the `Yield` strategy, which is fine for desktop and console
 //! games that aren't as affected by extra CPU usage. For mobile devices, the `Sleep` strategy
@@ -62,19 +64,32 @@
 //! `SleepAndYield` can potentially be as accurate as `Yield` while using less CPU time, but you
 //! will have to test different grace period timings to determine how much time needs to be left
 //! to ensure that the main thread doesn't sleep too long and miss the start of the next frame.
+//! `Adaptive` finds this grace period on its own, at the cost of occasionally missing the start of
+//! a frame while it adjusts to the system.
+//!
+//! # Frame Statistics
+//!
+//! The [`FrameStats`] resource keeps the durations of the last frames, so that frame pacing can be
+//! displayed or recorded with the minimum, maximum and percentile frame times rather than only an
+//! average frame rate.
 //!
 //! [`Application`]: ../../amethyst/struct.Application.html
 //! [`FrameRateLimitStrategy`]: ./enum.FrameRateLimitStrategy.html
+//! [`FrameStats`]: ./struct.FrameStats.html
 //! [`thread::yield_now`]: https://doc.rust-lang.org/std/thread/fn.yield_now.html
 //! [`thread::sleep`]: https://doc.rust-lang.org/stable/std/thread/fn.sleep.html
 
 use std::{
+    collections::VecDeque,
     thread::{sleep, yield_now},
     time::{Duration, Instant},
 };
 
 const ZERO: Duration = Duration::from_millis(0);
 
+/// Initial sleep overshoot estimate of the `Adaptive` strategy.
+const INITIAL_SLEEP_MARGIN: Duration = Duration::from_millis(2);
+
 /// Frame rate limiting strategy.
 ///
 /// See the [module documentation] on the difference between sleeping and yielding, and when
@@ -97,6 +112,13 @@ pub enum FrameRateLimitStrategy {
     /// Will sleep repeatedly until the given duration remains, and then will yield repeatedly
     /// for the remaining frame time.
     SleepAndYield(Duration),
+
+    /// Use sleep and yield combined, adjusting the split automatically.
+    ///
+    /// Will sleep until the expected sleep overshoot remains, and then will yield repeatedly for
+    /// the remaining frame time. The expected overshoot follows the largest recent overshoot, and
+    /// slowly decreases while the operating system wakes the game on time.
+    Adaptive,
 }
 
 impl Default for FrameRateLimitStrategy {
@@ -147,6 +169,7 @@ pub struct FrameLimiter {
     frame_duration: Duration,
     strategy: FrameRateLimitStrategy,
     last_call: Instant,
+    sleep_margin: Duration,
 }
 
 impl Default for FrameLimiter {
@@ -162,6 +185,7 @@ impl FrameLimiter {
             frame_duration: Duration::from_secs(0),
             strategy: Default::default(),
             last_call: Instant::now(),
+            sleep_margin: INITIAL_SLEEP_MARGIN,
         };
         s.set_rate(strategy, fps);
         s
@@ -182,6 +206,12 @@ impl FrameLimiter {
         Self::new(config.strategy, config.fps)
     }
 
+    /// Returns the time the `Adaptive` strategy currently leaves for yielding at the end of a
+    /// frame, which is its estimate of how late the operating system wakes the game up.
+    pub fn sleep_margin(&self) -> Duration {
+        self.sleep_margin
+    }
+
     /// Resets the frame start time to the current instant.
     ///
     /// This resets the frame limiter's internal tracking of when the last frame started to the
@@ -210,6 +240,11 @@ impl FrameLimiter {
                 self.do_sleep(dur);
                 self.do_yield();
             }
+
+            Adaptive => {
+                self.do_adaptive_sleep();
+                self.do_yield();
+            }
         }
         self.last_call = Instant::now();
     }
@@ -226,4 +261,196 @@ impl FrameLimiter {
             sleep(ZERO);
         }
     }
+
+    fn do_adaptive_sleep(&mut self) {
+        let elapsed = Instant::now() - self.last_call;
+        let sleep_until = self.frame_duration.checked_sub(self.sleep_margin);
+        let requested = match sleep_until.and_then(|until| until.checked_sub(elapsed)) {
+            Some(requested) if requested > ZERO => requested,
+            _ => return,
+        };
+        let before = Instant::now();
+        sleep(requested);
+        let overshoot = (Instant::now() - before)
+            .checked_sub(requested)
+            .unwrap_or(ZERO);
+        self.sleep_margin = adjust_sleep_margin(self.sleep_margin, overshoot, self.frame_duration);
+    }
+}
+
+/// Computes the next sleep margin of the `Adaptive` strategy from the overshoot of the last sleep.
+///
+/// The margin jumps to larger overshoots right away, as waking up late makes the frame miss its
+/// deadline, and decays by a sixteenth of the difference towards smaller ones.
+fn adjust_sleep_margin(
+    margin: Duration,
+    overshoot: Duration,
+    frame_duration: Duration,
+) -> Duration {
+    let margin = if overshoot > margin {
+        overshoot
+    } else {
+        margin - (margin - overshoot) / 16
+    };
+    margin.min(frame_duration)
+}
+
+/// Frame pacing statistics resource.
+///
+/// Keeps the durations of the last frames in a rolling window, which is updated by the
+/// application after every frame. Unlike the average frame rate, the maximum and high percentiles
+/// show stutters caused by occasional long frames.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let stats = world.read_resource::<FrameStats>();
+/// if let (Some(median), Some(worst)) = (stats.percentile(50.0), stats.percentile(99.0)) {
+///     println!("median: {:?}, 99th percentile: {:?}", median, worst);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    window: usize,
+    frames: VecDeque<Duration>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats::new(240)
+    }
+}
+
+impl FrameStats {
+    /// Creates new frame statistics over the given number of frames. The window is at least one
+    /// frame long.
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        FrameStats {
+            window,
+            frames: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Returns the number of frames the statistics are computed over.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Changes the number of frames the statistics are computed over, dropping the oldest frames
+    /// if the window shrinks.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+        while self.frames.len() > self.window {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Records the duration of a frame, dropping the oldest frame if the window is full.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn push(&mut self, frame: Duration) {
+        if self.frames.len() == self.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Forgets all recorded frames, e.g. after loading a level.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Returns the number of recorded frames, which is at most the window.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if no frame has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns an iterator over the recorded frame durations, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.frames.iter().cloned()
+    }
+
+    /// Returns the duration of the last frame.
+    pub fn last(&self) -> Option<Duration> {
+        self.frames.back().cloned()
+    }
+
+    /// Returns the shortest frame in the window.
+    pub fn min(&self) -> Option<Duration> {
+        self.frames.iter().min().cloned()
+    }
+
+    /// Returns the longest frame in the window.
+    pub fn max(&self) -> Option<Duration> {
+        self.frames.iter().max().cloned()
+    }
+
+    /// Returns the mean frame duration in the window.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let total = self.frames.iter().fold(ZERO, |total, frame| total + *frame);
+        Some(total / self.frames.len() as u32)
+    }
+
+    /// Returns the frame duration below which the given percentage of the frames in the window
+    /// are, using the nearest-rank method. `percent` is clamped between 0 and 100.
+    pub fn percentile(&self, percent: f32) -> Option<Duration> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let mut sorted = self.frames.iter().cloned().collect::<Vec<_>>();
+        sorted.sort();
+        let percent = percent.max(0.0).min(100.0);
+        let rank = (percent / 100.0 * sorted.len() as f32).ceil() as usize;
+        Some(sorted[rank.max(1).min(sorted.len()) - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn sleep_margin_follows_overshoot() {
+        let frame = millis(16);
+        assert_eq!(millis(5), adjust_sleep_margin(millis(2), millis(5), frame));
+        assert_eq!(
+            millis(15),
+            adjust_sleep_margin(millis(16), millis(0), frame)
+        );
+        assert_eq!(frame, adjust_sleep_margin(millis(2), millis(20), frame));
+    }
+
+    #[test]
+    fn frame_stats_over_rolling_window() {
+        let mut stats = FrameStats::new(4);
+        assert_eq!(None, stats.percentile(50.0));
+        for frame in &[50, 10, 40, 20, 30] {
+            stats.push(millis(*frame));
+        }
+
+        assert_eq!(4, stats.len());
+        assert_eq!(Some(millis(10)), stats.min());
+        assert_eq!(Some(millis(40)), stats.max());
+        assert_eq!(Some(millis(25)), stats.mean());
+        assert_eq!(Some(millis(20)), stats.percentile(50.0));
+        assert_eq!(Some(millis(40)), stats.percentile(99.0));
+        assert_eq!(Some(millis(10)), stats.percentile(0.0));
+
+        stats.set_window(2);
+        assert_eq!(Some(millis(20)), stats.min());
+    }
 }
@@ -8,12 +8,16 @@ pub extern crate shred;
 pub extern crate shrev;
 pub extern crate specs;
 
+#[cfg(feature = "saveload")]
+extern crate bincode;
 #[macro_use]
 extern crate error_chain;
 extern crate fnv;
 extern crate hibitset;
 extern crate log;
 extern crate rayon;
+#[cfg(feature = "saveload")]
+extern crate ron;
 #[macro_use]
 extern crate serde;
 extern crate specs_hierarchy;
@@ -36,14 +40,21 @@ pub use {
 
 pub use self::{
     axis::{Axis2, Axis3},
+    entity_event::{EntityEvent, EntityEventSystem},
+    event_bus::{EventBus, ListenerId, Propagation},
     named::{Named, WithNamed},
 };
 
 mod axis;
 pub mod bundle;
+mod entity_event;
 mod event;
+mod event_bus;
 pub mod frame_limiter;
 mod named;
+#[cfg(feature = "saveload")]
+pub mod saveload;
+pub mod spatial;
 pub mod timing;
 pub mod transform;
 
@@ -0,0 +1,310 @@
+//! Saving and loading the components of marked entities, available with the `saveload` feature.
+//!
+//! Entities are marked for saving with a `SaveMarker`. A snapshot contains the chosen components
+//! of all marked entities, and can be restored into a fresh or existing `World`. Entity references,
+//! such as the one stored in `Parent`, are saved as markers and remapped to the restored entities.
+
+use bincode;
+use ron::{self, ser::PrettyConfig};
+use specs::{
+    error::NoError,
+    prelude::{
+        DispatcherBuilder, Entities, Entity, ReadStorage, System, SystemData, World, Write,
+        WriteStorage,
+    },
+    saveload::{
+        ConvertSaveload, DeserializeComponents, EntityData, Marker, MarkerAllocator,
+        SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+    },
+    Join,
+};
+
+use {
+    bundle::{Result as BundleResult, SystemBundle},
+    transform::Parent,
+};
+
+error_chain! {
+    foreign_links {
+        Ron(ron::ser::Error) #[doc = "Failed to serialize a RON snapshot."];
+        RonDe(ron::de::Error) #[doc = "Failed to deserialize a RON snapshot."];
+        Bincode(bincode::Error) #[doc = "Failed to serialize or deserialize a bincode snapshot."];
+    }
+
+    errors {
+        /// A saved component refers to an entity without a `SaveMarker`.
+        UnmarkedEntity {
+            description("Saved component refers to an entity which is not marked for saving")
+            display("Saved component refers to an entity which is not marked for saving")
+        }
+    }
+}
+
+impl From<NoError> for Error {
+    fn from(err: NoError) -> Self {
+        match err {}
+    }
+}
+
+/// Tag type for the `SaveMarker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Saved;
+
+/// Marks an entity to be included in snapshots.
+///
+/// Use `MarkedBuilder::marked::<SaveMarker>()` when building an entity, or
+/// `SaveMarkerAllocator::mark` for existing entities.
+pub type SaveMarker = SimpleMarker<Saved>;
+
+/// Allocates `SaveMarker`s, and maps them back to entities when loading a snapshot.
+pub type SaveMarkerAllocator = SimpleMarkerAllocator<Saved>;
+
+/// The serialization format of a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotFormat {
+    /// Human readable RON, suitable for level checkpoints which are edited by hand.
+    Ron,
+    /// Compact binary format, suitable for save games.
+    Bincode,
+}
+
+impl<M> ConvertSaveload<M> for Parent
+where
+    M: Marker,
+{
+    type Data = M;
+    type Error = Error;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<M>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        ids(self.entity).ok_or_else(|| ErrorKind::UnmarkedEntity.into())
+    }
+
+    fn convert_from<F>(data: M, mut ids: F) -> Result<Self>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        ids(data)
+            .map(|entity| Parent { entity })
+            .ok_or_else(|| ErrorKind::UnmarkedEntity.into())
+    }
+}
+
+/// Serializes the components `C` of all entities marked with a `SaveMarker`.
+///
+/// Parents of marked entities are marked as well, so the hierarchy can be restored.
+///
+/// `C` is a tuple of `ReadStorage`s of the components to save. Components which are
+/// `Clone + Serialize + DeserializeOwned` can be saved as is, components referring to other
+/// entities need to implement `ConvertSaveload`, like `Parent` does.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let snapshot = save::<(ReadStorage<Transform>, ReadStorage<Parent>, ReadStorage<Named>)>(
+///     &world,
+///     SnapshotFormat::Ron,
+/// )?;
+/// ```
+pub fn save<'a, C>(world: &'a World, format: SnapshotFormat) -> Result<Vec<u8>>
+where
+    C: SystemData<'a> + SerializeComponents<Error, SaveMarker>,
+{
+    let (entities, mut markers, mut allocator, parents, components) = <(
+        Entities<'a>,
+        WriteStorage<'a, SaveMarker>,
+        Write<'a, SaveMarkerAllocator>,
+        ReadStorage<'a, Parent>,
+        C,
+    )>::fetch(&world.res);
+
+    let mut unvisited = (&*entities, &markers)
+        .join()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    while let Some(entity) = unvisited.pop() {
+        if let Some(parent) = parents.get(entity) {
+            if markers.get(parent.entity).is_none() {
+                allocator.mark(parent.entity, &mut markers);
+                unvisited.push(parent.entity);
+            }
+        }
+    }
+
+    let data = (&*entities, &markers)
+        .join()
+        .map(|(entity, marker)| {
+            Ok(EntityData {
+                marker: marker.clone(),
+                components: components
+                    .serialize_entity(entity, |entity| markers.get(entity).cloned())?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match format {
+        SnapshotFormat::Ron => {
+            Ok(ron::ser::to_string_pretty(&data, PrettyConfig::default())?.into_bytes())
+        }
+        SnapshotFormat::Bincode => Ok(bincode::serialize(&data)?),
+    }
+}
+
+/// Restores the components `C` of the entities in a snapshot made with `save`.
+///
+/// Entities whose marker is already known by the `SaveMarkerAllocator` get their components
+/// overwritten, other entities are created. The components and the `SaveLoadBundle` must have
+/// been registered in the world, and `World::maintain` must be called for the created entities
+/// to show up in joins, which the application does every frame.
+///
+/// `C` is a tuple of `WriteStorage`s of the same components as the ones saved.
+///
+/// # Returns
+///
+/// The restored entities.
+pub fn load<'a, C>(world: &'a World, snapshot: &[u8], format: SnapshotFormat) -> Result<Vec<Entity>>
+where
+    C: SystemData<'a> + DeserializeComponents<Error, SaveMarker>,
+{
+    let data: Vec<EntityData<SaveMarker, <C as DeserializeComponents<Error, SaveMarker>>::Data>> =
+        match format {
+            SnapshotFormat::Ron => ron::de::from_bytes(snapshot)?,
+            SnapshotFormat::Bincode => bincode::deserialize(snapshot)?,
+        };
+
+    let (entities, mut markers, mut allocator, mut components) = <(
+        Entities<'a>,
+        WriteStorage<'a, SaveMarker>,
+        Write<'a, SaveMarkerAllocator>,
+        C,
+    )>::fetch(&world.res);
+
+    let mut loaded = Vec::with_capacity(data.len());
+    for entity_data in data {
+        let entity = allocator.retrieve_entity(entity_data.marker, &mut markers, &entities);
+        components.deserialize_entity(entity, entity_data.components, |marker| {
+            Some(allocator.retrieve_entity(marker, &mut markers, &entities))
+        })?;
+        loaded.push(entity);
+    }
+    Ok(loaded)
+}
+
+/// Frees the markers of deleted entities.
+///
+/// Registers the `SaveMarker` storage and the `SaveMarkerAllocator` resource.
+#[derive(Default)]
+pub struct SaveMarkerSystem;
+
+impl<'a> System<'a> for SaveMarkerSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, SaveMarker>,
+        Write<'a, SaveMarkerAllocator>,
+    );
+
+    fn run(&mut self, (entities, markers, mut allocator): Self::SystemData) {
+        allocator.maintain(&entities, &markers);
+    }
+}
+
+/// Save/load bundle
+///
+/// Will register the `SaveMarker` component, the `SaveMarkerAllocator` resource and the
+/// `SaveMarkerSystem`. `SaveMarkerSystem` will be registered with name "save_marker_system".
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+///
+/// ## Panics
+///
+/// Panics in `SaveMarkerSystem` registration if the bundle is applied twice in the same
+/// dispatcher.
+#[derive(Default)]
+pub struct SaveLoadBundle;
+
+impl SaveLoadBundle {
+    /// Create a new save/load bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for SaveLoadBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> BundleResult<()> {
+        builder.add(SaveMarkerSystem, "save_marker_system", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::saveload::MarkedBuilder;
+
+    use super::*;
+    use {named::Named, transform::Transform};
+
+    type Saving<'a> = (
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Named>,
+    );
+    type Loading<'a> = (
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, Named>,
+    );
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+        world.register::<Named>();
+        world.register::<SaveMarker>();
+        world.add_resource(SaveMarkerAllocator::default());
+        world
+    }
+
+    fn round_trip(format: SnapshotFormat) {
+        let mut world = world();
+        let parent = world.create_entity().with(Named::new("parent")).build();
+        let mut transform = Transform::default();
+        transform.set_x(3.0);
+        world
+            .create_entity()
+            .with(Named::new("child"))
+            .with(Parent { entity: parent })
+            .with(transform)
+            .marked::<SaveMarker>()
+            .build();
+
+        let snapshot = save::<Saving>(&world, format).unwrap();
+
+        let mut restored = world();
+        restored.create_entity().build();
+        let loaded = load::<Loading>(&restored, &snapshot, format).unwrap();
+        restored.maintain();
+        assert_eq!(2, loaded.len());
+
+        let names = restored.read_storage::<Named>();
+        let parents = restored.read_storage::<Parent>();
+        let transforms = restored.read_storage::<Transform>();
+        let (child, parent) = (&*restored.entities(), &parents).join().next().unwrap();
+        assert_eq!("child", names.get(child).unwrap().name);
+        assert_eq!("parent", names.get(parent.entity).unwrap().name);
+        assert_eq!(3.0, transforms.get(child).unwrap().translation().x);
+    }
+
+    #[test]
+    fn ron_snapshot_keeps_hierarchy() {
+        round_trip(SnapshotFormat::Ron);
+    }
+
+    #[test]
+    fn bincode_snapshot_keeps_hierarchy() {
+        round_trip(SnapshotFormat::Bincode);
+    }
+}
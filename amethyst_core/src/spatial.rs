@@ -0,0 +1,658 @@
+//! Spatial index of the entities with a `GlobalTransform`, for fast proximity queries.
+//!
+//! The `SpatialIndex` resource sorts entities into a uniform grid of cubic (or square, in 2D)
+//! cells, so that queries only look at the entities in nearby cells instead of every entity. It is
+//! opt-in: add the `SpatialIndexBundle` after the `TransformBundle` to keep it in sync with the
+//! `GlobalTransform` changes.
+//!
+//! Entities are indexed by the translation of their `GlobalTransform`. The cell size should be in
+//! the order of the typical query radius: too small cells make queries visit many empty cells, too
+//! large cells make them check many entities which are too far away.
+
+use std::{cmp::Ordering, marker::PhantomData};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use nalgebra::Vector3;
+use specs::{
+    prelude::{
+        ComponentEvent, DispatcherBuilder, Entities, Entity, Join, ReadStorage, ReaderId,
+        Resources, System, SystemData, Write, WriteStorage,
+    },
+    world::Index,
+};
+
+use {
+    bundle::{Result, SystemBundle},
+    transform::GlobalTransform,
+};
+
+/// The dimensions of a `SpatialIndex`.
+pub trait Dimensions: Send + Sync + 'static {
+    /// True if the index ignores the z coordinate.
+    const PLANAR: bool;
+}
+
+/// Marker for a `SpatialIndex` of the x and y coordinates, for 2D games.
+pub struct Dim2;
+
+impl Dimensions for Dim2 {
+    const PLANAR: bool = true;
+}
+
+/// Marker for a `SpatialIndex` of the x, y and z coordinates.
+pub struct Dim3;
+
+impl Dimensions for Dim3 {
+    const PLANAR: bool = false;
+}
+
+/// Spatial index of 2D positions, the z coordinates of entities and queries are ignored.
+pub type SpatialIndex2D = SpatialIndex<Dim2>;
+
+/// Spatial index of 3D positions.
+pub type SpatialIndex3D = SpatialIndex<Dim3>;
+
+type Cell = [i32; 3];
+
+/// Uniform grid of the positions of entities, supporting radius, box, ray and nearest neighbour
+/// queries.
+///
+/// The index is updated by the `SpatialIndexSystem<D>`, and can also be used on its own with
+/// `insert` and `remove`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let index = world.read_resource::<SpatialIndex3D>();
+/// for entity in index.within_radius(listener_position, 50.0) {
+///     // Only entities around the listener are audible.
+/// }
+/// ```
+pub struct SpatialIndex<D = Dim3> {
+    cell_size: f32,
+    cells: FnvHashMap<Cell, Vec<Entity>>,
+    positions: FnvHashMap<Index, (Entity, Vector3<f32>, Cell)>,
+    marker: PhantomData<D>,
+}
+
+impl<D: Dimensions> Default for SpatialIndex<D> {
+    fn default() -> Self {
+        SpatialIndex::new(10.0)
+    }
+}
+
+impl<D: Dimensions> SpatialIndex<D> {
+    /// Creates a new, empty index with the given cell size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size isn't positive.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "Spatial index cell size must be positive");
+        SpatialIndex {
+            cell_size,
+            cells: FnvHashMap::default(),
+            positions: FnvHashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the size of the cells of the grid.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Returns the number of indexed entities.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns true if no entity is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the indexed position of the entity.
+    pub fn position(&self, entity: Entity) -> Option<Vector3<f32>> {
+        self.positions
+            .get(&entity.id())
+            .filter(|(indexed, _, _)| *indexed == entity)
+            .map(|(_, position, _)| *position)
+    }
+
+    /// Inserts the entity at the given position, or moves it there if it is already indexed.
+    pub fn insert(&mut self, entity: Entity, position: Vector3<f32>) {
+        let position = Self::project(position);
+        let cell = self.cell(&position);
+        if let Some(&(indexed, _, old_cell)) = self.positions.get(&entity.id()) {
+            if indexed == entity && old_cell == cell {
+                self.positions.insert(entity.id(), (entity, position, cell));
+                return;
+            }
+            self.remove_index(entity.id());
+        }
+        self.positions.insert(entity.id(), (entity, position, cell));
+        self.cells.entry(cell).or_insert_with(Vec::new).push(entity);
+    }
+
+    /// Removes the entity from the index.
+    ///
+    /// # Returns
+    ///
+    /// True if the entity was indexed.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        self.remove_index(entity.id())
+    }
+
+    /// Removes all entities from the index.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    /// Returns the entities at most `radius` away from `center`.
+    pub fn within_radius(&self, center: Vector3<f32>, radius: f32) -> Vec<Entity> {
+        let center = Self::project(center);
+        let extent = Vector3::repeat(radius);
+        let radius_squared = radius * radius;
+        let mut found = Vec::new();
+        self.for_each_in_box(
+            &(center - extent),
+            &(center + extent),
+            |entity, position| {
+                if (position - center).norm_squared() <= radius_squared {
+                    found.push(entity);
+                }
+            },
+        );
+        found
+    }
+
+    /// Returns the entities inside the axis-aligned box going from `min` to `max`.
+    pub fn in_aabb(&self, min: Vector3<f32>, max: Vector3<f32>) -> Vec<Entity> {
+        let (min, max) = (Self::project(min), Self::project(max));
+        let mut found = Vec::new();
+        self.for_each_in_box(&min, &max, |entity, position| {
+            if (0..3).all(|axis| position[axis] >= min[axis] && position[axis] <= max[axis]) {
+                found.push(entity);
+            }
+        });
+        found
+    }
+
+    /// Returns the entities at most `radius` away from the ray starting at `origin`, going in
+    /// `direction` for `max_distance`, with their distance along the ray, closest first.
+    ///
+    /// Entities are points, so `radius` is the size of what is being picked. `max_distance` can
+    /// be infinite, while a negative or NaN distance or radius finds nothing.
+    pub fn raycast(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        radius: f32,
+    ) -> Vec<(Entity, f32)> {
+        if !(max_distance >= 0.0 && radius >= 0.0) {
+            // Also rejects NaN distances and radiuses.
+            return Vec::new();
+        }
+        let origin = Self::project(origin);
+        let direction = match Self::project(direction).try_normalize(::std::f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Vec::new(),
+        };
+        let radius_squared = radius * radius;
+        let mut found = Vec::new();
+        {
+            let mut check = |entity: Entity, position: &Vector3<f32>| {
+                let distance = (position - origin).dot(&direction);
+                if distance < 0.0 || distance > max_distance {
+                    return;
+                }
+                let closest = origin + direction * distance;
+                if (position - closest).norm_squared() <= radius_squared {
+                    found.push((entity, distance));
+                }
+            };
+
+            // Sampling the ray every half cell, the ray only goes through cells next to the
+            // sampled ones, widened by the radius.
+            let rings = (radius / self.cell_size).ceil() + 1.0;
+            let step = self.cell_size / 2.0;
+            let samples = (max_distance / step).ceil() + 1.0;
+            let side = 2.0 * rings + 1.0;
+            let ring_cells = if D::PLANAR {
+                side.powi(2)
+            } else {
+                side.powi(3)
+            };
+            if samples * ring_cells > self.cells.len() as f32 {
+                // Long or wide rays, including infinite ones, are faster to check against the
+                // indexed entities.
+                for &(entity, ref position, _) in self.positions.values() {
+                    check(entity, position);
+                }
+            } else {
+                let mut visited = FnvHashSet::default();
+                for sample in 0..samples as usize {
+                    let point = origin + direction * (sample as f32 * step).min(max_distance);
+                    let center = self.cell(&point);
+                    for cell in self.cells_around(center, rings as i32) {
+                        if !visited.insert(cell) {
+                            continue;
+                        }
+                        for (entity, position) in self.entities_in(cell) {
+                            check(entity, position);
+                        }
+                    }
+                }
+            }
+        }
+        sort_by_distance(&mut found);
+        found
+    }
+
+    /// Returns the `k` entities closest to `point`, with their distance, closest first.
+    pub fn nearest(&self, point: Vector3<f32>, k: usize) -> Vec<(Entity, f32)> {
+        let point = Self::project(point);
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let center = self.cell(&point);
+        let mut found = Vec::new();
+        let mut ring = 0;
+        loop {
+            let side = (2 * ring + 1) as usize;
+            let ring_cells = if D::PLANAR { side.pow(2) } else { side.pow(3) };
+            if k >= self.positions.len() || ring_cells > self.cells.len() {
+                // Visiting the rings would go through more cells than there are occupied ones.
+                found = self
+                    .positions
+                    .values()
+                    .map(|(entity, position, _)| (*entity, (position - point).norm()))
+                    .collect();
+                break;
+            }
+            for cell in self.cells_around(center, ring) {
+                if (0..3).any(|axis| (cell[axis] - center[axis]).abs() == ring) {
+                    found.extend(
+                        self.entities_in(cell)
+                            .map(|(entity, position)| (entity, (position - point).norm())),
+                    );
+                }
+            }
+            // Entities in the following rings are at least `ring` cells away from the point.
+            if found.len() >= k {
+                sort_by_distance(&mut found);
+                if found[k - 1].1 <= ring as f32 * self.cell_size {
+                    break;
+                }
+            }
+            ring += 1;
+        }
+        sort_by_distance(&mut found);
+        found.truncate(k);
+        found
+    }
+
+    fn entities_in<'a>(
+        &'a self,
+        cell: Cell,
+    ) -> impl Iterator<Item = (Entity, &'a Vector3<f32>)> + 'a {
+        self.cells
+            .get(&cell)
+            .into_iter()
+            .flat_map(|entities| entities.iter())
+            .map(move |entity| (*entity, &self.positions[&entity.id()].1))
+    }
+
+    fn project(mut position: Vector3<f32>) -> Vector3<f32> {
+        if D::PLANAR {
+            position.z = 0.0;
+        }
+        position
+    }
+
+    fn cell(&self, position: &Vector3<f32>) -> Cell {
+        [
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        ]
+    }
+
+    /// Returns the cells at most `rings` cells away from `center` on each axis.
+    fn cells_around(&self, center: Cell, rings: i32) -> Vec<Cell> {
+        let z_rings = if D::PLANAR { 0 } else { rings };
+        let mut cells = Vec::new();
+        for x in -rings..=rings {
+            for y in -rings..=rings {
+                for z in -z_rings..=z_rings {
+                    cells.push([center[0] + x, center[1] + y, center[2] + z]);
+                }
+            }
+        }
+        cells
+    }
+
+    fn for_each_in_box<F>(&self, min: &Vector3<f32>, max: &Vector3<f32>, mut f: F)
+    where
+        F: FnMut(Entity, &Vector3<f32>),
+    {
+        let cell_size = f64::from(self.cell_size);
+        // The cells of each axis, unless they don't fit in a `Cell`, e.g. for infinite bounds.
+        let cell_range = |axis: usize| {
+            let low = (f64::from(min[axis]) / cell_size).floor();
+            let high = (f64::from(max[axis]) / cell_size).floor();
+            let fits = |cell: f64| cell >= f64::from(i32::MIN) && cell <= f64::from(i32::MAX);
+            if fits(low) && fits(high) {
+                Some((low as i32, high as i32))
+            } else {
+                None
+            }
+        };
+        let ranges = [cell_range(0), cell_range(1), cell_range(2)];
+        let box_cells = ranges.iter().try_fold(1i64, |cells, range| {
+            let (low, high) = (*range)?;
+            cells.checked_mul((i64::from(high) - i64::from(low) + 1).max(0))
+        });
+        match (box_cells, ranges) {
+            (Some(box_cells), [Some(x), Some(y), Some(z)])
+                if box_cells <= self.cells.len() as i64 =>
+            {
+                for x in x.0..=x.1 {
+                    for y in y.0..=y.1 {
+                        for z in z.0..=z.1 {
+                            for (entity, position) in self.entities_in([x, y, z]) {
+                                f(entity, position);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                // Large or unbounded boxes are faster to check against the indexed entities.
+                for &(entity, ref position, _) in self.positions.values() {
+                    if (0..3).all(|axis| position[axis] >= min[axis] && position[axis] <= max[axis])
+                    {
+                        f(entity, position);
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove_index(&mut self, id: Index) -> bool {
+        match self.positions.remove(&id) {
+            Some((_, _, cell)) => {
+                Self::remove_from_cell(&mut self.cells, cell, id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_from_cell(cells: &mut FnvHashMap<Cell, Vec<Entity>>, cell: Cell, id: Index) {
+        let empty = match cells.get_mut(&cell) {
+            Some(entities) => {
+                entities.retain(|entity| entity.id() != id);
+                entities.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            cells.remove(&cell);
+        }
+    }
+}
+
+/// Keeps the `SpatialIndex<D>` resource in sync with the `GlobalTransform` components.
+///
+/// ### Type parameters:
+///
+/// - `D`: the dimensions of the index, `Dim2` or `Dim3`
+pub struct SpatialIndexSystem<D> {
+    cell_size: f32,
+    events_id: Option<ReaderId<ComponentEvent>>,
+    initialized: bool,
+    marker: PhantomData<D>,
+}
+
+impl<D: Dimensions> SpatialIndexSystem<D> {
+    /// Creates a new `SpatialIndexSystem`, inserting a `SpatialIndex` with the given cell size
+    /// during setup if there isn't one yet.
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndexSystem {
+            cell_size,
+            events_id: None,
+            initialized: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, D: Dimensions> System<'a> for SpatialIndexSystem<D> {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, GlobalTransform>,
+        Write<'a, SpatialIndex<D>>,
+    );
+
+    fn run(&mut self, (entities, globals, mut index): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("spatial_index_system");
+
+        let events =
+            globals.channel().read(self.events_id.as_mut().expect(
+                "`SpatialIndexSystem::setup` was not called before `SpatialIndexSystem::run`",
+            ));
+        if !self.initialized {
+            // Transforms inserted before the system was set up were not reported.
+            self.initialized = true;
+            events.for_each(drop);
+            index.clear();
+            for (entity, global) in (&*entities, &globals).join() {
+                index.insert(entity, translation(global));
+            }
+            return;
+        }
+
+        for event in events {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    let entity = entities.entity(*id);
+                    match globals.get(entity) {
+                        Some(global) => index.insert(entity, translation(global)),
+                        None => {
+                            index.remove_index(*id);
+                        }
+                    }
+                }
+                ComponentEvent::Removed(id) => {
+                    index.remove_index(*id);
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        let cell_size = self.cell_size;
+        res.entry::<SpatialIndex<D>>()
+            .or_insert_with(|| SpatialIndex::new(cell_size));
+        Self::SystemData::setup(res);
+        self.events_id = Some(WriteStorage::<GlobalTransform>::fetch(res).register_reader());
+    }
+}
+
+fn sort_by_distance(found: &mut Vec<(Entity, f32)>) {
+    found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+}
+
+fn translation(global: &GlobalTransform) -> Vector3<f32> {
+    Vector3::new(global.0[(0, 3)], global.0[(1, 3)], global.0[(2, 3)])
+}
+
+/// Spatial index bundle
+///
+/// Will register the `SpatialIndexSystem<D>` and the `SpatialIndex<D>` resource.
+/// `SpatialIndexSystem<D>` will be registered with name "spatial_index_system". It should run
+/// after the "transform_system", which is the default dependency.
+///
+/// ### Type parameters:
+///
+/// - `D`: the dimensions of the index, `Dim2` or `Dim3`
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+///
+/// ## Panics
+///
+/// Panics in `SpatialIndexSystem` registration if the bundle is applied twice in the same
+/// dispatcher.
+pub struct SpatialIndexBundle<'a, D> {
+    cell_size: f32,
+    dep: &'a [&'a str],
+    marker: PhantomData<D>,
+}
+
+impl<'a, D> SpatialIndexBundle<'a, D> {
+    /// Create a new spatial index bundle with the given cell size
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndexBundle {
+            cell_size,
+            dep: &["transform_system"],
+            marker: PhantomData,
+        }
+    }
+
+    /// Set dependencies for the `SpatialIndexSystem`
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, 'b, 'c, D: Dimensions> SystemBundle<'a, 'b> for SpatialIndexBundle<'c, D> {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add(
+            SpatialIndexSystem::<D>::new(self.cell_size),
+            "spatial_index_system",
+            self.dep,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Matrix4;
+    use shred::RunNow;
+    use specs::prelude::{Builder, World};
+
+    use super::*;
+
+    fn global(x: f32, y: f32, z: f32) -> GlobalTransform {
+        GlobalTransform(Matrix4::new_translation(&Vector3::new(x, y, z)))
+    }
+
+    #[test]
+    fn queries() {
+        let mut world = World::new();
+        let entities = (0..5)
+            .map(|_| world.create_entity().build())
+            .collect::<Vec<_>>();
+        let mut index = SpatialIndex3D::new(2.0);
+        for (i, entity) in entities.iter().enumerate() {
+            index.insert(*entity, Vector3::new(i as f32 * 3.0, 0.0, 0.0));
+        }
+        // Moves entity 4 from x = 12 next to entity 0.
+        index.insert(entities[4], Vector3::new(0.0, 1.0, 0.0));
+
+        let mut near = index.within_radius(Vector3::new(1.0, 0.0, 0.0), 2.5);
+        near.sort();
+        assert_eq!(vec![entities[0], entities[1], entities[4]], near);
+        let mut inside = index.in_aabb(Vector3::new(5.0, -1.0, -1.0), Vector3::new(10.0, 1.0, 1.0));
+        inside.sort();
+        assert_eq!(vec![entities[2], entities[3]], inside);
+        assert_eq!(
+            vec![(entities[1], 2.0), (entities[2], 5.0), (entities[3], 8.0)],
+            index.raycast(
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                100.0,
+                0.5
+            )
+        );
+        assert_eq!(
+            3,
+            index
+                .raycast(
+                    Vector3::new(1.0, 0.0, 0.0),
+                    Vector3::new(1.0, 0.0, 0.0),
+                    ::std::f32::INFINITY,
+                    0.5
+                )
+                .len()
+        );
+        assert!(index
+            .raycast(
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                ::std::f32::NAN,
+                0.5
+            )
+            .is_empty());
+        for &bound in &[::std::f32::INFINITY, ::std::f32::MAX] {
+            assert_eq!(
+                5,
+                index
+                    .in_aabb(Vector3::repeat(-bound), Vector3::repeat(bound))
+                    .len()
+            );
+            assert_eq!(5, index.within_radius(Vector3::zeros(), bound).len());
+        }
+        let nearest = index.nearest(Vector3::new(7.0, 0.0, 0.0), 2);
+        assert_eq!(
+            vec![entities[2], entities[3]],
+            nearest.iter().map(|n| n.0).collect::<Vec<_>>()
+        );
+
+        assert!(index.remove(entities[2]));
+        assert!(!index.remove(entities[2]));
+        assert_eq!(
+            entities[3],
+            index.nearest(Vector3::new(7.0, 0.0, 0.0), 1)[0].0
+        );
+    }
+
+    #[test]
+    fn system_follows_global_transforms() {
+        let mut world = World::new();
+        let mut system = SpatialIndexSystem::<Dim2>::new(1.0);
+        system.setup(&mut world.res);
+        let first = world.create_entity().with(global(0.0, 0.0, 5.0)).build();
+        system.run_now(&world.res);
+        assert_eq!(
+            Some(Vector3::new(0.0, 0.0, 0.0)),
+            world.read_resource::<SpatialIndex2D>().position(first)
+        );
+
+        let second = world.create_entity().with(global(3.0, 0.0, 0.0)).build();
+        *world
+            .write_storage::<GlobalTransform>()
+            .get_mut(first)
+            .unwrap() = global(4.0, 0.0, 0.0);
+        system.run_now(&world.res);
+        let mut found = world
+            .read_resource::<SpatialIndex2D>()
+            .within_radius(Vector3::new(3.5, 0.0, 9.0), 1.0);
+        found.sort();
+        assert_eq!(vec![first, second], found);
+
+        world.delete_entity(second).unwrap();
+        system.run_now(&world.res);
+        assert_eq!(1, world.read_resource::<SpatialIndex2D>().len());
+    }
+}
@@ -1,6 +1,13 @@
 //! Utilities for working with time.
 
-use std::time::{Duration, Instant};
+use std::{
+    cmp, iter,
+    time::{Duration, Instant},
+};
+
+use fnv::FnvHashMap;
+use shrev::EventChannel;
+use specs::prelude::World;
 
 /// Frame timing values.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -19,6 +26,15 @@ pub struct Time {
     fixed_time: Duration,
     /// Time at which `State::fixed_update` was last called.
     pub last_fixed_update: Instant,
+    /// Real time accumulated since the last `State::fixed_update`, which is yet to be consumed by
+    /// fixed updates.
+    fixed_time_accumulator: Duration,
+    /// The total number of fixed updates that have been run in this session.
+    fixed_update_count: u64,
+    /// The maximum number of fixed updates run in a single frame.
+    max_fixed_updates: u32,
+    /// The number of fixed updates run in the current frame.
+    frame_fixed_updates: u32,
     /// The total number of frames that have been played in this session.
     frame_number: u64,
     ///Time elapsed since game start, ignoring the speed multipler.
@@ -95,6 +111,28 @@ impl Time {
         self.time_scale
     }
 
+    /// Gets the total number of fixed updates that have been run in this session.
+    pub fn fixed_update_count(&self) -> u64 {
+        self.fixed_update_count
+    }
+
+    /// Gets the maximum number of fixed updates run in a single frame.
+    pub fn max_fixed_updates(&self) -> u32 {
+        self.max_fixed_updates
+    }
+
+    /// Gets how far the current frame is between the last fixed update and the next one, from
+    /// `0.0` right after a fixed update to `1.0` right before the next one.
+    ///
+    /// This is the fraction of a fixed time step left in the accumulator once the fixed updates
+    /// of the frame have run, and can be used to interpolate state updated in `fixed_update`.
+    pub fn interpolation_alpha(&self) -> f32 {
+        if self.fixed_time == Duration::from_secs(0) {
+            return 0.0;
+        }
+        (duration_to_secs(self.fixed_time_accumulator) / self.fixed_seconds).min(1.0)
+    }
+
     /// Gets the total number of frames that have been played in this session.
     /// Sets both `delta_seconds` and `delta_time` based on the seconds given.
     ///
@@ -136,6 +174,15 @@ impl Time {
         self.fixed_time = time;
     }
 
+    /// Sets the maximum number of fixed updates run in a single frame.
+    ///
+    /// When a frame took so long that more fixed updates would be needed to catch up, e.g. after
+    /// loading or a breakpoint, the remaining whole time steps are dropped. This keeps slow
+    /// frames from causing even more fixed updates in the next frames.
+    pub fn set_max_fixed_updates(&mut self, max: u32) {
+        self.max_fixed_updates = max;
+    }
+
     /// Increments the current frame number by 1.
     ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
@@ -156,12 +203,52 @@ impl Time {
         self.time_scale = multiplier;
     }
 
+    /// Adds the real time elapsed during the last frame to the fixed update accumulator.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn start_fixed_update(&mut self) {
+        self.fixed_time_accumulator += self.delta_real_time;
+        self.frame_fixed_updates = 0;
+    }
+
+    /// Checks whether enough time has been accumulated to run another fixed update, and if so
+    /// consumes one fixed time step from the accumulator.
+    ///
+    /// Since the accumulator only depends on the delta times the engine has been given, the
+    /// number of fixed updates is the same for the same sequence of delta times.
+    ///
+    /// At most `max_fixed_updates` steps are consumed per frame, the whole time steps left after
+    /// that are dropped.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn step_fixed_update(&mut self) -> bool {
+        if self.fixed_time_accumulator < self.fixed_time {
+            return false;
+        }
+        if self.frame_fixed_updates >= self.max_fixed_updates {
+            // Only keep the fraction of a time step, so that the interpolation stays valid.
+            let fixed_nanos = duration_to_nanos(self.fixed_time);
+            self.fixed_time_accumulator = if fixed_nanos == 0 {
+                Duration::from_secs(0)
+            } else {
+                nanos_to_duration(duration_to_nanos(self.fixed_time_accumulator) % fixed_nanos)
+            };
+            return false;
+        }
+        self.fixed_time_accumulator -= self.fixed_time;
+        self.frame_fixed_updates += 1;
+        true
+    }
+
     /// Indicates a fixed update just finished.
     ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn finish_fixed_update(&mut self) {
-        self.last_fixed_update += self.fixed_time
+        self.last_fixed_update += self.fixed_time;
+        self.fixed_update_count += 1;
     }
 }
 
@@ -175,6 +262,10 @@ impl Default for Time {
             fixed_seconds: duration_to_secs(Duration::new(0, 16_666_666)),
             fixed_time: Duration::new(0, 16_666_666),
             last_fixed_update: Instant::now(),
+            fixed_time_accumulator: Duration::from_secs(0),
+            fixed_update_count: 0,
+            max_fixed_updates: 8,
+            frame_fixed_updates: 0,
             frame_number: 0,
             absolute_real_time: Duration::default(),
             absolute_time: Duration::default(),
@@ -251,12 +342,364 @@ impl Stopwatch {
     }
 }
 
+/// A clock with its own time scale which can be paused, e.g. for the gameplay, the UI or
+/// cutscenes.
+///
+/// Domains are advanced by the real time elapsed during a frame, so they are independent of
+/// `Time::time_scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeDomain {
+    time_scale: f32,
+    paused: bool,
+    delta_time: Duration,
+    absolute_time: Duration,
+}
+
+impl TimeDomain {
+    /// Creates a new running time domain with a time scale of 1.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Gets the time elapsed in this domain during the last frame in seconds.
+    pub fn delta_seconds(&self) -> f32 {
+        duration_to_secs(self.delta_time)
+    }
+
+    /// Gets the time elapsed in this domain during the last frame.
+    pub fn delta_time(&self) -> Duration {
+        self.delta_time
+    }
+
+    /// Gets the time elapsed in this domain since it was created in seconds.
+    pub fn absolute_seconds(&self) -> f64 {
+        duration_to_secs_f64(self.absolute_time)
+    }
+
+    /// Gets the time elapsed in this domain since it was created.
+    pub fn absolute_time(&self) -> Duration {
+        self.absolute_time
+    }
+
+    /// Gets the time multiplier of this domain.
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets the time multiplier of this domain.
+    ///
+    /// ## Panics
+    /// This will panic if multiplier is NaN, Infinity, or less than 0.
+    pub fn set_time_scale(&mut self, multiplier: f32) {
+        use std::f32::INFINITY;
+        assert!(multiplier >= 0.0);
+        assert!(multiplier != INFINITY);
+        self.time_scale = multiplier;
+    }
+
+    /// Returns true if the domain is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the domain. No time elapses in a paused domain.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    fn advance(&mut self, delta_real_time: Duration) {
+        self.delta_time = if self.paused {
+            Duration::from_secs(0)
+        } else {
+            secs_to_duration(duration_to_secs(delta_real_time) * self.time_scale)
+        };
+        self.absolute_time += self.delta_time;
+    }
+}
+
+impl Default for TimeDomain {
+    fn default() -> Self {
+        TimeDomain {
+            time_scale: 1.0,
+            paused: false,
+            delta_time: Duration::from_secs(0),
+            absolute_time: Duration::from_secs(0),
+        }
+    }
+}
+
+/// A resource holding named `TimeDomain`s.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// // Slow down the game, but not the menus.
+/// domains.domain_mut("gameplay").set_time_scale(0.5);
+/// // In a gameplay system.
+/// let delta = domains.delta_seconds("gameplay");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TimeDomains {
+    domains: FnvHashMap<String, TimeDomain>,
+}
+
+impl TimeDomains {
+    /// Creates an empty `TimeDomains`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the domain with the given name, if it exists.
+    pub fn get(&self, name: &str) -> Option<&TimeDomain> {
+        self.domains.get(name)
+    }
+
+    /// Returns the domain with the given name, creating it if it doesn't exist.
+    pub fn domain_mut<N: Into<String>>(&mut self, name: N) -> &mut TimeDomain {
+        self.domains
+            .entry(name.into())
+            .or_insert_with(TimeDomain::new)
+    }
+
+    /// Removes the domain with the given name and returns it.
+    pub fn remove(&mut self, name: &str) -> Option<TimeDomain> {
+        self.domains.remove(name)
+    }
+
+    /// Gets the time elapsed during the last frame in the given domain in seconds, or 0 if the
+    /// domain doesn't exist.
+    pub fn delta_seconds(&self, name: &str) -> f32 {
+        self.get(name).map_or(0.0, TimeDomain::delta_seconds)
+    }
+
+    /// Gets the time elapsed during the last frame in the given domain, or zero if the domain
+    /// doesn't exist.
+    pub fn delta_time(&self, name: &str) -> Duration {
+        self.get(name)
+            .map_or(Duration::from_secs(0), TimeDomain::delta_time)
+    }
+
+    /// Advances all domains by the real time elapsed during the last frame.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn advance(&mut self, delta_real_time: Duration) {
+        for domain in self.domains.values_mut() {
+            domain.advance(delta_real_time);
+        }
+    }
+}
+
+/// The type of a function called when a `Timer` fires.
+pub type TimerCallback = Box<FnMut(&mut World) + Send + Sync>;
+
+/// Identifies a timer added to the `Timers` resource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+/// An event written to the `EventChannel<TimerEvent>` resource each time a timer fires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerEvent {
+    /// The timer which fired.
+    pub id: TimerId,
+}
+
+/// A timer firing once after a duration, or repeatedly on an interval.
+///
+/// The time is counted in a `TimeDomain`, or using `Time::delta_time` if no domain is set. The
+/// timers of a paused or missing domain don't advance.
+pub struct Timer {
+    duration: Duration,
+    repeat: bool,
+    domain: Option<String>,
+    elapsed: Duration,
+    callback: Option<TimerCallback>,
+}
+
+impl Timer {
+    /// Creates a timer firing once after `duration`.
+    pub fn after(duration: Duration) -> Self {
+        Timer {
+            duration,
+            repeat: false,
+            domain: None,
+            elapsed: Duration::from_secs(0),
+            callback: None,
+        }
+    }
+
+    /// Creates a timer firing every `interval`, until it is cancelled.
+    pub fn every(interval: Duration) -> Self {
+        Timer {
+            repeat: true,
+            ..Timer::after(interval)
+        }
+    }
+
+    /// Counts the time in the `TimeDomain` with the given name.
+    pub fn in_domain<N: Into<String>>(mut self, domain: N) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Sets a function called each time the timer fires.
+    pub fn with_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut World) + Send + Sync + 'static,
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Advances the timer and returns the number of times it fired.
+    fn advance(&mut self, delta: Duration) -> u32 {
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            0
+        } else if !self.repeat || self.duration == Duration::from_secs(0) {
+            self.elapsed = Duration::from_secs(0);
+            1
+        } else {
+            let elapsed = duration_to_nanos(self.elapsed);
+            let interval = duration_to_nanos(self.duration);
+            self.elapsed = nanos_to_duration(elapsed % interval);
+            cmp::min(elapsed / interval, u64::from(u32::MAX)) as u32
+        }
+    }
+}
+
+/// A resource holding `Timer`s, which fire `TimerEvent`s and call their callbacks once they
+/// elapse.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let id = world.write_resource::<Timers>().add(
+///     Timer::every(Duration::from_secs(5))
+///         .in_domain("gameplay")
+///         .with_callback(|world| spawn_wave(world)),
+/// );
+/// ```
+#[derive(Default)]
+pub struct Timers {
+    timers: Vec<(TimerId, Timer)>,
+    next_id: u64,
+}
+
+impl Timers {
+    /// Creates an empty `Timers`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a timer and returns its id.
+    pub fn add(&mut self, timer: Timer) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push((id, timer));
+        id
+    }
+
+    /// Cancels a timer. Returns false if the timer was not found, e.g. because it already fired.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let len = self.timers.len();
+        self.timers.retain(|&(timer_id, _)| timer_id != id);
+        self.timers.len() != len
+    }
+
+    /// Returns the time left until the timer fires next, or `None` if it was not found.
+    pub fn remaining(&self, id: TimerId) -> Option<Duration> {
+        self.timers
+            .iter()
+            .find(|&&(timer_id, _)| timer_id == id)
+            .map(|&(_, ref timer)| timer.duration - timer.elapsed)
+    }
+
+    /// Returns the number of active timers.
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Returns true if there are no active timers.
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Advances the timers of the `Timers` resource of the world, writes a `TimerEvent` for each
+    /// timer which fired and calls their callbacks.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn update(world: &mut World) {
+        let delta_time = world
+            .res
+            .try_fetch::<Time>()
+            .map_or(Duration::from_secs(0), |time| time.delta_time());
+
+        let mut fired = Vec::new();
+        {
+            let domains = world.res.try_fetch::<TimeDomains>();
+            let mut timers = match world.res.try_fetch_mut::<Timers>() {
+                Some(timers) => timers,
+                None => return,
+            };
+            let mut remaining = Vec::with_capacity(timers.timers.len());
+            for (id, mut timer) in timers.timers.drain(..) {
+                let delta = match timer.domain {
+                    Some(ref domain) => domains
+                        .as_ref()
+                        .map_or(Duration::from_secs(0), |domains| domains.delta_time(domain)),
+                    None => delta_time,
+                };
+                let count = timer.advance(delta);
+                if count > 0 {
+                    fired.push((id, count, timer.callback.take()));
+                }
+                if count == 0 || timer.repeat {
+                    remaining.push((id, timer));
+                }
+            }
+            timers.timers = remaining;
+        }
+
+        if let Some(mut events) = world.res.try_fetch_mut::<EventChannel<TimerEvent>>() {
+            for &(id, count, _) in &fired {
+                events.iter_write(iter::repeat(TimerEvent { id }).take(count as usize));
+            }
+        }
+
+        for (id, count, callback) in fired {
+            if let Some(mut callback) = callback {
+                for _ in 0..count {
+                    callback(world);
+                }
+                // Give the callback back to repeating timers, unless they were cancelled or a
+                // callback removed the `Timers` resource.
+                let mut timers = match world.res.try_fetch_mut::<Timers>() {
+                    Some(timers) => timers,
+                    None => continue,
+                };
+                if let Some(&mut (_, ref mut timer)) = timers
+                    .timers
+                    .iter_mut()
+                    .find(|&&mut (timer_id, _)| timer_id == id)
+                {
+                    timer.callback = Some(callback);
+                }
+            }
+        }
+    }
+}
+
 // Unit tests
 #[cfg(test)]
 mod tests {
     use std::{thread, time::Duration};
 
-    use super::Stopwatch;
+    use specs::prelude::World;
+
+    use super::{Stopwatch, Time, TimeDomains, Timer, TimerEvent, Timers};
+    use shrev::EventChannel;
 
     #[test]
     fn elapsed() {
@@ -350,6 +793,118 @@ mod tests {
             elapsed
         );
     }
+
+    fn count_fixed_updates(time: &mut Time, delta: Duration) -> u32 {
+        time.set_delta_time(delta);
+        time.start_fixed_update();
+        let mut count = 0;
+        while time.step_fixed_update() {
+            time.finish_fixed_update();
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn fixed_update_accumulates_delta_time() {
+        let mut time = Time::default();
+        time.set_fixed_time(Duration::from_millis(10));
+
+        assert_eq!(0, count_fixed_updates(&mut time, Duration::from_millis(5)));
+        assert_eq!(1, count_fixed_updates(&mut time, Duration::from_millis(5)));
+        assert_eq!(2, count_fixed_updates(&mut time, Duration::from_millis(25)));
+        assert_eq!(1, count_fixed_updates(&mut time, Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn fixed_update_ignores_time_scale() {
+        let mut time = Time::default();
+        time.set_fixed_time(Duration::from_millis(10));
+        time.set_time_scale(0.5);
+
+        assert_eq!(3, count_fixed_updates(&mut time, Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn interpolation_alpha_is_accumulator_fraction() {
+        let mut time = Time::default();
+        time.set_fixed_time(Duration::from_millis(10));
+
+        assert_eq!(2, count_fixed_updates(&mut time, Duration::from_millis(25)));
+        assert_eq!(2, time.fixed_update_count());
+        assert!((time.interpolation_alpha() - 0.5).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn fixed_updates_are_capped_after_a_hitch() {
+        let mut time = Time::default();
+        time.set_fixed_time(Duration::from_millis(10));
+        time.set_max_fixed_updates(4);
+
+        assert_eq!(
+            4,
+            count_fixed_updates(&mut time, Duration::from_millis(10_005))
+        );
+        assert!((time.interpolation_alpha() - 0.5).abs() < 1.0e-4);
+        assert_eq!(1, count_fixed_updates(&mut time, Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn time_domains_scale_and_pause() {
+        let mut domains = TimeDomains::new();
+        domains.domain_mut("gameplay").set_time_scale(0.5);
+        domains.domain_mut("ui").set_paused(true);
+
+        domains.advance(Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(50), domains.delta_time("gameplay"));
+        assert_eq!(Duration::from_secs(0), domains.delta_time("ui"));
+        assert_eq!(Duration::from_secs(0), domains.delta_time("cutscene"));
+    }
+
+    #[test]
+    fn repeating_timer_fires_events_and_callbacks() {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.add_resource(TimeDomains::new());
+        world.add_resource(Timers::new());
+        world.add_resource(EventChannel::<TimerEvent>::new());
+        world.add_resource(0u32);
+        world.write_resource::<TimeDomains>().domain_mut("gameplay");
+        let mut reader = world
+            .write_resource::<EventChannel<TimerEvent>>()
+            .register_reader();
+
+        let id = world.write_resource::<Timers>().add(
+            Timer::every(Duration::from_millis(10))
+                .in_domain("gameplay")
+                .with_callback(|world| *world.write_resource::<u32>() += 1),
+        );
+        world
+            .write_resource::<TimeDomains>()
+            .advance(Duration::from_millis(25));
+        Timers::update(&mut world);
+
+        assert_eq!(2, *world.read_resource::<u32>());
+        assert_eq!(
+            2,
+            world
+                .read_resource::<EventChannel<TimerEvent>>()
+                .read(&mut reader)
+                .filter(|event| event.id == id)
+                .count()
+        );
+        assert_eq!(
+            Some(Duration::from_millis(5)),
+            world.read_resource::<Timers>().remaining(id)
+        );
+    }
+
+    #[test]
+    fn short_interval_timer_after_long_frame() {
+        let mut timer = Timer::every(Duration::new(0, 1_000));
+        assert_eq!(3_600_000_000, timer.advance(Duration::new(3_600, 500)));
+        assert_eq!(Duration::new(0, 500), timer.elapsed);
+    }
 }
 
 /// Converts a Duration to the time in seconds.
@@ -13,6 +13,9 @@ use {
 /// Will register transform components, and the `TransformSystem`.
 /// `TransformSystem` will be registered with name "transform_system".
 ///
+/// With interpolation enabled, the `TransformInterpolationSystem` will be registered with name
+/// "transform_interpolation_system", before the `TransformSystem`.
+///
 /// ## Errors
 ///
 /// No errors will be returned by this bundle.
@@ -24,6 +27,7 @@ use {
 #[derive(Default)]
 pub struct TransformBundle<'a> {
     dep: &'a [&'a str],
+    interpolation: bool,
 }
 
 impl<'a> TransformBundle<'a> {
@@ -37,6 +41,13 @@ impl<'a> TransformBundle<'a> {
         self.dep = dep;
         self
     }
+
+    /// Interpolate the `GlobalTransform` of entities with a `TransformInterpolation` between
+    /// fixed updates
+    pub fn with_interpolation(mut self, interpolation: bool) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
 }
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
@@ -46,11 +57,24 @@ impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TransformBundle<'c> {
             "parent_hierarchy_system",
             self.dep,
         );
-        builder.add(
-            TransformSystem::new(),
-            "transform_system",
-            &["parent_hierarchy_system"],
-        );
+        if self.interpolation {
+            builder.add(
+                TransformInterpolationSystem::new(),
+                "transform_interpolation_system",
+                self.dep,
+            );
+            builder.add(
+                TransformSystem::new(),
+                "transform_system",
+                &["parent_hierarchy_system", "transform_interpolation_system"],
+            );
+        } else {
+            builder.add(
+                TransformSystem::new(),
+                "transform_system",
+                &["parent_hierarchy_system"],
+            );
+        }
         Ok(())
     }
 }
@@ -0,0 +1,77 @@
+//! Interpolation of the transform of an entity between fixed updates.
+
+use nalgebra::Matrix4;
+use specs::prelude::{Component, DenseVecStorage};
+
+use transform::Transform;
+
+/// Interpolates the `GlobalTransform` of an entity between the `Transform`s it had after its last
+/// two fixed updates, using `Time::interpolation_alpha`.
+///
+/// Entities moved in `State::fixed_update` would otherwise stutter when rendering runs at a
+/// different rate. The `Transform` itself is left untouched, only the `GlobalTransform` lags
+/// behind it by up to one fixed time step.
+///
+/// This requires the `TransformBundle` to be created `with_interpolation`.
+#[derive(Clone, Debug, Default)]
+pub struct TransformInterpolation {
+    previous: Option<Transform>,
+    current: Option<Transform>,
+    blended: Option<Matrix4<f32>>,
+}
+
+impl TransformInterpolation {
+    /// Creates a new `TransformInterpolation`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Forgets the recorded transforms, so that the next transform is displayed without
+    /// interpolating from the previous one, e.g. after teleporting an entity.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.current = None;
+        self.blended = None;
+    }
+
+    /// Returns the interpolated local matrix, if two transforms have been recorded.
+    pub fn matrix(&self) -> Option<Matrix4<f32>> {
+        self.blended
+    }
+
+    /// Returns true if no transform has been recorded since the last reset.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Records the transform of the entity after a fixed update.
+    pub(crate) fn record(&mut self, transform: &Transform) {
+        self.previous = self.current.take();
+        self.current = Some(transform.clone());
+    }
+
+    /// Updates the interpolated matrix, `alpha` going from the previous transform at `0.0` to the
+    /// current one at `1.0`.
+    pub(crate) fn blend(&mut self, alpha: f32) {
+        self.blended = match (&self.previous, &self.current) {
+            (Some(previous), Some(current)) => Some(lerp(previous, current, alpha).matrix()),
+            _ => None,
+        };
+    }
+}
+
+impl Component for TransformInterpolation {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn lerp(from: &Transform, to: &Transform, alpha: f32) -> Transform {
+    let mut transform = to.clone();
+    *transform.translation_mut() =
+        from.translation() + (to.translation() - from.translation()) * alpha;
+    *transform.scale_mut() = from.scale() + (to.scale() - from.scale()) * alpha;
+    // Slerping between opposite rotations is undefined, snap to the target in that case.
+    if let Some(rotation) = from.rotation().try_slerp(to.rotation(), alpha, 1.0e-6) {
+        *transform.rotation_mut() = rotation;
+    }
+    transform
+}
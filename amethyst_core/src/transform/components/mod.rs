@@ -1,11 +1,13 @@
 //! Components for the transform processor.
 
 pub use self::{
+    interpolation::TransformInterpolation,
     local_transform::Transform,
     parent::{HierarchyEvent, Parent, ParentHierarchy},
     transform::GlobalTransform,
 };
 
+mod interpolation;
 mod local_transform;
 mod parent;
 mod transform;
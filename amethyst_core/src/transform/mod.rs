@@ -1,7 +1,13 @@
 //! `amethyst` transform ecs module
 
-pub use self::{bundle::TransformBundle, components::*, systems::*};
+pub use self::{
+    bundle::TransformBundle,
+    components::*,
+    scene_graph::{Ancestors, ReparentError, SceneGraph},
+    systems::*,
+};
 
 pub mod bundle;
 pub mod components;
+pub mod scene_graph;
 pub mod systems;
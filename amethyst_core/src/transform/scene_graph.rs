@@ -0,0 +1,315 @@
+//! Queries on the scene graph formed by `Parent` components.
+
+use std::{error, fmt, ops::Deref};
+
+use nalgebra::Vector3;
+use specs::{
+    error::WrongGeneration,
+    prelude::{Entity, WriteStorage},
+    storage::MaskedStorage,
+    world::EntitiesRes,
+    Storage,
+};
+
+use {
+    named::Named,
+    transform::{Parent, ParentHierarchy, Transform},
+};
+
+/// Extension trait of the `ParentHierarchy` to walk and edit the scene graph.
+///
+/// The hierarchy is updated by the "parent_hierarchy_system" of the `TransformBundle`, so `Parent`
+/// components changed during the current frame are not taken into account yet.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let hand = hierarchy.find_by_path(character, "body/arm_l/hand", &names);
+/// if let Some(hand) = hand {
+///     hierarchy.reparent(sword, Some(hand), &mut parents, &mut locals)?;
+/// }
+/// ```
+pub trait SceneGraph {
+    /// Returns an iterator over the parent, grand-parent etc. of the entity, up to the root.
+    fn ancestors(&self, entity: Entity) -> Ancestors;
+
+    /// Finds a descendant of `root` by following the `Named` children on a path separated by
+    /// slashes, e.g. `"body/arm_l/hand"`.
+    ///
+    /// If several children have the same name, the first one is used.
+    fn find_by_path<D>(
+        &self,
+        root: Entity,
+        path: &str,
+        names: &Storage<Named, D>,
+    ) -> Option<Entity>
+    where
+        D: Deref<Target = MaskedStorage<Named>>;
+
+    /// Computes the world-space transform of the entity from the `Transform`s of the entity and
+    /// its ancestors, without waiting for the `TransformSystem`.
+    ///
+    /// Returns `None` if the entity has no `Transform`. Ancestors without a `Transform` are
+    /// skipped.
+    fn world_transform<D>(
+        &self,
+        entity: Entity,
+        locals: &Storage<Transform, D>,
+    ) -> Option<Transform>
+    where
+        D: Deref<Target = MaskedStorage<Transform>>;
+
+    /// Changes the parent of the entity, or makes it a root if `new_parent` is `None`, and updates
+    /// its `Transform` so that its world-space transform is kept.
+    ///
+    /// The world-space transform is kept exactly as long as the ancestors have uniform scales.
+    fn reparent(
+        &self,
+        entity: Entity,
+        new_parent: Option<Entity>,
+        parents: &mut WriteStorage<Parent>,
+        locals: &mut WriteStorage<Transform>,
+    ) -> Result<(), ReparentError>;
+
+    /// Deletes the entity and all of its descendants.
+    ///
+    /// Like `EntitiesRes::delete`, the entities are removed once the world is maintained.
+    /// Descendants which are already dead are ignored.
+    fn delete_with_descendants(
+        &self,
+        entity: Entity,
+        entities: &EntitiesRes,
+    ) -> Result<(), WrongGeneration>;
+}
+
+impl SceneGraph for ParentHierarchy {
+    fn ancestors(&self, entity: Entity) -> Ancestors {
+        Ancestors {
+            hierarchy: self,
+            current: entity,
+        }
+    }
+
+    fn find_by_path<D>(&self, root: Entity, path: &str, names: &Storage<Named, D>) -> Option<Entity>
+    where
+        D: Deref<Target = MaskedStorage<Named>>,
+    {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(root, |entity, segment| {
+                self.children(entity).iter().cloned().find(|child| {
+                    names
+                        .get(*child)
+                        .map_or(false, |named| named.name == segment)
+                })
+            })
+    }
+
+    fn world_transform<D>(
+        &self,
+        entity: Entity,
+        locals: &Storage<Transform, D>,
+    ) -> Option<Transform>
+    where
+        D: Deref<Target = MaskedStorage<Transform>>,
+    {
+        let mut world = locals.get(entity)?.clone();
+        for ancestor in self.ancestors(entity) {
+            if let Some(local) = locals.get(ancestor) {
+                let mut parent = local.clone();
+                parent.concat(&world);
+                world = parent;
+            }
+        }
+        Some(world)
+    }
+
+    fn reparent(
+        &self,
+        entity: Entity,
+        new_parent: Option<Entity>,
+        parents: &mut WriteStorage<Parent>,
+        locals: &mut WriteStorage<Transform>,
+    ) -> Result<(), ReparentError> {
+        if let Some(new_parent) = new_parent {
+            if new_parent == entity
+                || self
+                    .ancestors(new_parent)
+                    .any(|ancestor| ancestor == entity)
+            {
+                return Err(ReparentError::Cycle);
+            }
+        }
+
+        if let Some(world) = self.world_transform(entity, locals) {
+            let local = match new_parent.and_then(|parent| self.world_transform(parent, locals)) {
+                Some(parent_world) => {
+                    let mut local = inverse(&parent_world);
+                    local.concat(&world);
+                    local
+                }
+                None => world,
+            };
+            locals
+                .insert(entity, local)
+                .map_err(|_| ReparentError::DeadEntity)?;
+        }
+
+        match new_parent {
+            Some(parent) => {
+                parents
+                    .insert(entity, Parent { entity: parent })
+                    .map_err(|_| ReparentError::DeadEntity)?;
+            }
+            None => {
+                parents.remove(entity);
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_with_descendants(
+        &self,
+        entity: Entity,
+        entities: &EntitiesRes,
+    ) -> Result<(), WrongGeneration> {
+        for child in self.all_children_iter(entity) {
+            // Children may have been deleted by the user already, which is fine.
+            let _ = entities.delete(child);
+        }
+        entities.delete(entity)
+    }
+}
+
+/// Iterator over the ancestors of an entity, see `SceneGraph::ancestors`.
+pub struct Ancestors<'a> {
+    hierarchy: &'a ParentHierarchy,
+    current: Entity,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let parent = self.hierarchy.parent(self.current)?;
+        self.current = parent;
+        Some(parent)
+    }
+}
+
+/// Error returned by `SceneGraph::reparent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReparentError {
+    /// The new parent is the entity itself or one of its descendants.
+    Cycle,
+    /// The entity is not alive anymore.
+    DeadEntity,
+}
+
+impl fmt::Display for ReparentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReparentError::Cycle => write!(f, "Entity can't be a descendant of itself"),
+            ReparentError::DeadEntity => write!(f, "Entity is not alive"),
+        }
+    }
+}
+
+impl error::Error for ReparentError {
+    fn description(&self) -> &str {
+        match *self {
+            ReparentError::Cycle => "Entity can't be a descendant of itself",
+            ReparentError::DeadEntity => "Entity is not alive",
+        }
+    }
+}
+
+/// Returns the transform which undoes `transform` when concatenated with it.
+fn inverse(transform: &Transform) -> Transform {
+    let scale = transform.scale();
+    let inverse_scale = Vector3::new(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z);
+    let rotation = transform.rotation().inverse();
+
+    let mut inverse = Transform::default();
+    inverse.set_scale(inverse_scale.x, inverse_scale.y, inverse_scale.z);
+    inverse.set_rotation(rotation);
+    *inverse.translation_mut() =
+        -(rotation * transform.translation().component_mul(&inverse_scale));
+    inverse
+}
+
+#[cfg(test)]
+mod tests {
+    use shred::RunNow;
+    use specs::prelude::{Builder, World};
+    use specs_hierarchy::HierarchySystem;
+
+    use super::*;
+    use named::Named;
+
+    fn transform(x: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.set_x(x);
+        transform
+    }
+
+    #[test]
+    fn find_and_reparent() {
+        let mut world = World::new();
+        let mut hierarchy_system = HierarchySystem::<Parent>::new();
+        hierarchy_system.setup(&mut world.res);
+        world.register::<Transform>();
+        world.register::<Named>();
+
+        let root = world.create_entity().with(transform(1.0)).build();
+        let arm = world
+            .create_entity()
+            .with(Named::new("arm"))
+            .with(Parent { entity: root })
+            .with(transform(2.0))
+            .build();
+        let hand = world
+            .create_entity()
+            .with(Named::new("hand"))
+            .with(Parent { entity: arm })
+            .with(transform(3.0))
+            .build();
+        hierarchy_system.run_now(&world.res);
+
+        let hierarchy = world.read_resource::<ParentHierarchy>();
+        let names = world.read_storage::<Named>();
+        let mut parents = world.write_storage::<Parent>();
+        let mut locals = world.write_storage::<Transform>();
+
+        assert_eq!(
+            vec![arm, root],
+            hierarchy.ancestors(hand).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(hand), hierarchy.find_by_path(root, "arm/hand", &names));
+        assert_eq!(None, hierarchy.find_by_path(root, "hand", &names));
+        assert_eq!(
+            6.0,
+            hierarchy
+                .world_transform(hand, &locals)
+                .unwrap()
+                .translation()
+                .x
+        );
+
+        assert_eq!(
+            Err(ReparentError::Cycle),
+            hierarchy.reparent(root, Some(hand), &mut parents, &mut locals)
+        );
+        hierarchy
+            .reparent(hand, Some(root), &mut parents, &mut locals)
+            .unwrap();
+        assert_eq!(root, parents.get(hand).unwrap().entity);
+        assert_eq!(5.0, locals.get(hand).unwrap().translation().x);
+
+        hierarchy
+            .delete_with_descendants(arm, &world.entities())
+            .unwrap();
+        assert!(!world.entities().is_alive(hand));
+        assert!(world.entities().is_alive(root));
+    }
+}
@@ -1,12 +1,18 @@
 //! Scene graph system and types
 
 use hibitset::BitSet;
+use nalgebra::Matrix4;
 use specs::prelude::{
-    ComponentEvent, Entities, Entity, Join, ReadExpect, ReadStorage, ReaderId, Resources, System,
-    WriteStorage,
+    ComponentEvent, Entities, Entity, Join, Read, ReadExpect, ReadStorage, ReaderId, Resources,
+    System, WriteStorage,
 };
 
-use transform::{GlobalTransform, HierarchyEvent, Parent, ParentHierarchy, Transform};
+use {
+    timing::Time,
+    transform::{
+        GlobalTransform, HierarchyEvent, Parent, ParentHierarchy, Transform, TransformInterpolation,
+    },
+};
 
 /// Handles updating `GlobalTransform` components based on the `Transform`
 /// component and parents.
@@ -40,9 +46,13 @@ impl<'a> System<'a> for TransformSystem {
         ReadExpect<'a, ParentHierarchy>,
         ReadStorage<'a, Transform>,
         ReadStorage<'a, Parent>,
+        ReadStorage<'a, TransformInterpolation>,
         WriteStorage<'a, GlobalTransform>,
     );
-    fn run(&mut self, (entities, hierarchy, locals, parents, mut globals): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, hierarchy, locals, parents, interpolations, mut globals): Self::SystemData,
+    ) {
         #[cfg(feature = "profiler")]
         profile_scope!("transform_system");
 
@@ -71,6 +81,11 @@ impl<'a> System<'a> for TransformSystem {
                 ComponentEvent::Removed(_id) => {}
             });
 
+        // Interpolated transforms change every frame.
+        for (entity, _) in (&*entities, &interpolations).join() {
+            self.local_modified.add(entity.id());
+        }
+
         for event in hierarchy.changed().read(
             self.parent_events_id
                 .as_mut()
@@ -100,7 +115,7 @@ impl<'a> System<'a> for TransformSystem {
             .join()
         {
             self.global_modified.add(entity.id());
-            global.0 = local.matrix();
+            global.0 = local_matrix(&interpolations, entity, local);
             debug_assert!(
                 global.is_finite(),
                 format!("Entity {:?} had a non-finite `Transform`", entity)
@@ -113,11 +128,12 @@ impl<'a> System<'a> for TransformSystem {
             if let (Some(parent), Some(local)) = (parents.get(*entity), locals.get(*entity)) {
                 let parent_dirty = self.global_modified.contains(parent.entity.id());
                 if parent_dirty || self_dirty {
+                    let local_matrix = local_matrix(&interpolations, *entity, local);
                     let combined_transform = if let Some(parent_global) = globals.get(parent.entity)
                     {
-                        (parent_global.0 * local.matrix())
+                        (parent_global.0 * local_matrix)
                     } else {
-                        local.matrix()
+                        local_matrix
                     };
 
                     if let Some(global) = globals.get_mut(*entity) {
@@ -139,6 +155,60 @@ impl<'a> System<'a> for TransformSystem {
     }
 }
 
+/// Returns the interpolated local matrix of the entity if it has one, or the matrix of its
+/// `Transform` otherwise.
+fn local_matrix(
+    interpolations: &ReadStorage<TransformInterpolation>,
+    entity: Entity,
+    local: &Transform,
+) -> Matrix4<f32> {
+    interpolations
+        .get(entity)
+        .and_then(TransformInterpolation::matrix)
+        .unwrap_or_else(|| local.matrix())
+}
+
+/// Records the `Transform` of entities with a `TransformInterpolation` after fixed updates, and
+/// blends them using `Time::interpolation_alpha`.
+///
+/// Only the transform after the last fixed update of a frame is recorded.
+#[derive(Default)]
+pub struct TransformInterpolationSystem {
+    fixed_update_count: Option<u64>,
+}
+
+impl TransformInterpolationSystem {
+    /// Creates a new transform interpolation system.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for TransformInterpolationSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        ReadStorage<'a, Transform>,
+        WriteStorage<'a, TransformInterpolation>,
+    );
+
+    fn run(&mut self, (time, locals, mut interpolations): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("transform_interpolation_system");
+
+        let fixed_update_count = time.fixed_update_count();
+        let stepped = self.fixed_update_count != Some(fixed_update_count);
+        self.fixed_update_count = Some(fixed_update_count);
+        let alpha = time.interpolation_alpha();
+
+        for (local, interpolation) in (&locals, &mut interpolations).join() {
+            if stepped || interpolation.is_empty() {
+                interpolation.record(local);
+            }
+            interpolation.blend(alpha);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use nalgebra::{Matrix4, Quaternion, Unit};
@@ -146,7 +216,13 @@ mod tests {
     use specs::prelude::{Builder, World};
     use specs_hierarchy::{Hierarchy, HierarchySystem};
 
-    use transform::{GlobalTransform, Parent, Transform, TransformSystem};
+    use std::time::Duration;
+
+    use timing::Time;
+    use transform::{
+        GlobalTransform, Parent, Transform, TransformInterpolation, TransformInterpolationSystem,
+        TransformSystem,
+    };
 
     // If this works, then all other tests should work.
     #[test]
@@ -489,4 +565,50 @@ mod tests {
         assert_eq!(world.is_alive(e4), false);
         assert_eq!(world.is_alive(e5), false);
     }
+
+    #[test]
+    fn interpolated() {
+        let (mut world, mut hs, mut system) = transform_world();
+        let mut interpolation_system = TransformInterpolationSystem::new();
+        interpolation_system.setup(&mut world.res);
+        world
+            .write_resource::<Time>()
+            .set_fixed_time(Duration::from_millis(10));
+
+        let e1 = world
+            .create_entity()
+            .with(Transform::default())
+            .with(TransformInterpolation::new())
+            .with(GlobalTransform::default())
+            .build();
+
+        interpolation_system.run_now(&mut world.res);
+        hs.run_now(&mut world.res);
+        system.run_now(&mut world.res);
+
+        // A fixed update moves the entity, and half a time step is left in the accumulator.
+        world
+            .write_storage::<Transform>()
+            .get_mut(e1)
+            .unwrap()
+            .set_x(10.0);
+        {
+            let mut time = world.write_resource::<Time>();
+            time.set_delta_time(Duration::from_millis(15));
+            time.start_fixed_update();
+            while time.step_fixed_update() {
+                time.finish_fixed_update();
+            }
+        }
+        interpolation_system.run_now(&mut world.res);
+        hs.run_now(&mut world.res);
+        system.run_now(&mut world.res);
+
+        let global = world
+            .read_storage::<GlobalTransform>()
+            .get(e1)
+            .unwrap()
+            .clone();
+        assert!((global.0[(0, 3)] - 5.0).abs() < 1.0e-3);
+    }
 }
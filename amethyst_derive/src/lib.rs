@@ -13,6 +13,9 @@ use syn::DeriveInput;
 mod event_reader;
 mod prefab_data;
 
+/// Deriving an `EventReader` for an enum generates the reader named by the `#[reader(..)]`
+/// attribute, reading every variant's event type from its `EventChannel`. Events published on the
+/// `EventBus` which no listener consumed are written to these channels, so they are read as well.
 #[proc_macro_derive(EventReader, attributes(reader))]
 pub fn event_reader_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
@@ -3,7 +3,7 @@
 extern crate amethyst;
 
 use amethyst::{
-    ecs::{Dispatcher, DispatcherBuilder},
+    ecs::System,
     prelude::*,
     shrev::EventChannel,
     Error,
@@ -30,25 +30,39 @@ impl SimpleState<'static, 'static> for StateA {
     }
 }
 
+/// A system which only runs while `StateB` is active.
+struct StateBSystem;
+
+impl<'a> System<'a> for StateBSystem {
+    type SystemData = ();
+
+    fn run(&mut self, _: Self::SystemData) {
+        println!("StateBSystem::run()");
+    }
+}
+
 /// StateB isn't Send + Sync
 struct StateB<'a> {
-    dispatcher: Dispatcher<'static, 'static>,
     _phantom: &'a PhantomData<()>,
 }
 
 impl<'a> Default for StateB<'a> {
     fn default() -> Self {
         StateB {
-            dispatcher: DispatcherBuilder::new().build(),
             _phantom: &PhantomData,
         }
     }
 }
 
 impl<'a> SimpleState<'static, 'static> for StateB<'a> {
-    fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'static, 'static> {
+    // The state machine sets these systems up when `StateB` starts, dispatches them after every
+    // `update` while `StateB` is the active state, and drops them when `StateB` stops.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        Some(GameDataBuilder::default().with(StateBSystem, "state_b_system", &[]))
+    }
+
+    fn update(&mut self, _data: &mut StateData<GameData>) -> SimpleTrans<'static, 'static> {
         println!("StateB::update()");
-        self.dispatcher.dispatch(&mut data.world.res);
         Trans::Quit
     }
 }
@@ -1,8 +1,19 @@
 //! The core engine framework.
 
-use std::{error::Error as StdError, marker::PhantomData, path::Path, sync::Arc, time::Duration};
+use std::{
+    error::Error as StdError,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use log::Level;
+use ctrlc;
 use rayon::ThreadPoolBuilder;
 use shred::Resource;
 
@@ -14,18 +25,22 @@ use {
     assets::{Loader, Source},
     callback_queue::CallbackQueue,
     core::{
-        frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
+        frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy, FrameStats},
         shrev::{EventChannel, ReaderId},
-        timing::{Stopwatch, Time},
-        EventReader, Named,
+        timing::{Stopwatch, Time, TimeDomains, TimerEvent, Timers},
+        EventBus, EventReader, Named,
     },
+    crash_report::CrashReporter,
     ecs::{
         common::Errors,
         prelude::{Component, Read, World, Write},
     },
     error::{Error, Result},
     game_data::DataInit,
-    state::{State, StateData, StateMachine, TransEvent},
+    logger::{LogBuffer, LogLevel},
+    sequence::{SequenceEvent, SequenceRunner},
+    shutdown::ShutdownHooks,
+    state::{State, StateData, StateLifecycleEvent, StateMachine, TransEvent},
     state_event::{StateEvent, StateEventReader},
     ui::UiEvent,
 };
@@ -55,11 +70,16 @@ where
     reader: R,
     #[derivative(Debug = "ignore")]
     events: Vec<E>,
-    event_reader_id: ReaderId<Event>,
+    event_reader_id: Option<ReaderId<Event>>,
     #[derivative(Debug = "ignore")]
     trans_reader_id: ReaderId<TransEvent<T, E>>,
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
+    #[derivative(Debug = "ignore")]
+    signal: Option<Arc<AtomicBool>>,
+    #[derivative(Debug = "ignore")]
+    crash_reporter: Option<CrashReporter>,
+    initialized: bool,
     data: T,
 }
 
@@ -218,32 +238,129 @@ where
     /// `Trans::Pop` on the last state in from the stack. See full
     /// documentation on this in [State](trait.State.html) documentation.
     ///
+    /// Once the game stopped running, the application runs its
+    /// [`ShutdownHooks`](struct.ShutdownHooks.html).
+    ///
+    /// # Returns
+    ///
+    /// The exit code given by `Trans::QuitWith`, or 0 if the game stopped in any other way.
+    ///
     /// # Examples
     ///
     /// See the example supplied in the
     /// [`new`](struct.Application.html#examples) method.
-    pub fn run(&mut self)
+    pub fn run(&mut self) -> i32
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
-        self.initialize();
+        if !self.initialized {
+            self.initialize();
+        } else if !self.states.is_running() {
+            // The application was already shut down by `step` or `run_frames`.
+            return self.states.exit_code();
+        }
         self.world.write_resource::<Stopwatch>().start();
         while self.states.is_running() {
-            self.advance_frame();
+            self.advance_frame_reported();
+            self.finish_frame();
+        }
 
-            self.world.write_resource::<FrameLimiter>().wait();
-            {
-                let elapsed = self.world.read_resource::<Stopwatch>().elapsed();
-                let mut time = self.world.write_resource::<Time>();
-                time.increment_frame_number();
-                time.set_delta_time(elapsed);
-            }
-            let mut stopwatch = self.world.write_resource::<Stopwatch>();
-            stopwatch.stop();
-            stopwatch.restart();
+        self.shutdown();
+        self.states.exit_code()
+    }
+
+    /// Run the gameloop for at most `frames` frames, or until the game state indicates that the
+    /// game is no longer running, whichever comes first.
+    ///
+    /// Any states still on the stack once the frame count is reached are stopped before the
+    /// application shuts down. Combined with [`ApplicationBuilder::headless`], this allows
+    /// running an application for a fixed number of ticks without a window, e.g. in tests.
+    ///
+    /// Returns the exit code like [`run`](#method.run).
+    ///
+    /// [`ApplicationBuilder::headless`]: struct.ApplicationBuilder.html#method.headless
+    pub fn run_frames(&mut self, frames: u64) -> i32
+    where
+        for<'b> R: EventReader<'b, Event = E>,
+    {
+        if !self.initialized {
+            self.initialize();
+        } else if !self.states.is_running() {
+            // The application was already shut down by `step` or `run_frames`.
+            return self.states.exit_code();
+        }
+        self.world.write_resource::<Stopwatch>().start();
+        let mut remaining = frames;
+        while self.states.is_running() && remaining > 0 {
+            self.advance_frame_reported();
+            self.finish_frame();
+            remaining -= 1;
         }
 
+        self.states
+            .stop(StateData::new(&mut self.world, &mut self.data));
         self.shutdown();
+        self.states.exit_code()
+    }
+
+    /// Advances the application by `frames` frames, as if `delta` had passed before each of them.
+    ///
+    /// Unlike [`run`](#method.run), this neither waits for the `FrameLimiter` nor measures the
+    /// real time taken by a frame: `Time` is updated with the given `delta` only. Fixed updates
+    /// are driven by the same delta, so stepping an application with the same sequence of deltas
+    /// always runs the same sequence of `update` and `fixed_update` calls. This makes it suitable
+    /// for replays, lockstep networking and golden tests.
+    ///
+    /// The application is initialized on the first call. If the game state stops running while
+    /// stepping, the remaining frames are skipped and the application is shut down.
+    ///
+    /// # Returns
+    ///
+    /// Whether the application is still running after stepping.
+    pub fn step(&mut self, frames: u64, delta: Duration) -> bool
+    where
+        for<'b> R: EventReader<'b, Event = E>,
+    {
+        if !self.initialized {
+            self.initialize();
+        }
+        for _ in 0..frames {
+            if !self.states.is_running() {
+                break;
+            }
+            self.world.write_resource::<Time>().set_delta_time(delta);
+            self.advance_frame_reported();
+            // Like `finish_frame`, so that frames have the same number as in a real run.
+            self.world.write_resource::<Time>().increment_frame_number();
+            if !self.states.is_running() {
+                self.shutdown();
+            }
+        }
+        self.states.is_running()
+    }
+
+    /// Advances a frame, writing a crash report if it panics and a `CrashReporter` is set.
+    fn advance_frame_reported(&mut self)
+    where
+        for<'b> R: EventReader<'b, Event = E>,
+    {
+        if self.crash_reporter.is_none() {
+            self.advance_frame();
+            return;
+        }
+
+        if let Some(ref reporter) = self.crash_reporter {
+            reporter.clear();
+        }
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.advance_frame())) {
+            if let Some(ref reporter) = self.crash_reporter {
+                match reporter.write(&self.world, self.states.state_names(), &*payload) {
+                    Ok(path) => error!("Crash report written to {}", path.display()),
+                    Err(err) => error!("Failed to write crash report: {}", err),
+                }
+            }
+            panic::resume_unwind(payload);
+        }
     }
 
     /// Sets up the application.
@@ -253,16 +370,16 @@ where
         self.states
             .start(StateData::new(&mut self.world, &mut self.data))
             .expect("Tried to start state machine without any states present");
+        self.initialized = true;
     }
 
     // React to window close events
     fn should_close(&mut self) -> bool {
         if self.ignore_window_close {
             false
-        } else {
+        } else if let Some(ref mut reader_id) = self.event_reader_id {
             use renderer::WindowEvent;
             let world = &mut self.world;
-            let reader_id = &mut self.event_reader_id;
             world.exec(|ev: Read<EventChannel<Event>>| {
                 ev.read(reader_id).any(|e| {
                     if cfg!(target_os = "ios") {
@@ -288,16 +405,26 @@ where
                     }
                 })
             })
+        } else {
+            // Headless applications have no window that could be closed.
+            false
         }
     }
 
+    // React to termination signals
+    fn received_signal(&self) -> bool {
+        self.signal
+            .as_ref()
+            .map_or(false, |signal| signal.load(Ordering::Relaxed))
+    }
+
     /// Advances the game world by one tick.
     fn advance_frame(&mut self)
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
         trace!("Advancing frame (`Application::advance_frame`)");
-        if self.should_close() {
+        if self.should_close() || self.received_signal() {
             let world = &mut self.world;
             let states = &mut self.states;
             states.stop(StateData::new(world, &mut self.data));
@@ -322,11 +449,29 @@ where
         {
             #[cfg(feature = "profiler")]
             profile_scope!("run_callback_queue");
-            let mut world = &mut self.world;
-            let receiver = world.read_resource::<CallbackQueue>().receiver.clone();
-            while let Ok(func) = receiver.try_recv() {
-                func(&mut world);
-            }
+            CallbackQueue::run(&mut self.world);
+        }
+
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("update_timers");
+            let delta_real_time = self.world.read_resource::<Time>().delta_real_time();
+            self.world
+                .write_resource::<TimeDomains>()
+                .advance(delta_real_time);
+            Timers::update(&mut self.world);
+        }
+
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("run_sequences");
+            SequenceRunner::update(&mut self.world);
+        }
+
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("dispatch_event_bus");
+            EventBus::dispatch(&self.world.res);
         }
 
         {
@@ -347,13 +492,10 @@ where
             }
         }
         {
-            let do_fixed = {
-                let time = self.world.write_resource::<Time>();
-                time.last_fixed_update().elapsed() >= time.fixed_time()
-            };
             #[cfg(feature = "profiler")]
             profile_scope!("fixed_update");
-            if do_fixed {
+            self.world.write_resource::<Time>().start_fixed_update();
+            while self.world.write_resource::<Time>().step_fixed_update() {
                 self.states
                     .fixed_update(StateData::new(&mut self.world, &mut self.data));
                 self.world.write_resource::<Time>().finish_fixed_update();
@@ -375,11 +517,27 @@ where
         self.world.write_resource::<Errors>().print_and_exit();
     }
 
+    /// Waits for the frame limiter and updates `Time` and `FrameStats` with the duration of the
+    /// last frame.
+    fn finish_frame(&mut self) {
+        self.world.write_resource::<FrameLimiter>().wait();
+        {
+            let elapsed = self.world.read_resource::<Stopwatch>().elapsed();
+            self.world.write_resource::<FrameStats>().push(elapsed);
+            let mut time = self.world.write_resource::<Time>();
+            time.increment_frame_number();
+            time.set_delta_time(elapsed);
+        }
+        let mut stopwatch = self.world.write_resource::<Stopwatch>();
+        stopwatch.stop();
+        stopwatch.restart();
+    }
+
     /// Cleans up after the quit signal is received.
     fn shutdown(&mut self) {
         info!("Engine is shutting down");
 
-        // Placeholder.
+        ShutdownHooks::run(&mut self.world);
     }
 }
 
@@ -404,6 +562,9 @@ pub struct ApplicationBuilder<S, T, E, R> {
     /// Used by bundles to access the world directly
     pub world: World,
     ignore_window_close: bool,
+    headless: bool,
+    handle_signals: bool,
+    crash_reporter: Option<CrashReporter>,
     phantom: PhantomData<(T, E, R)>,
 }
 
@@ -509,11 +670,22 @@ where
         world.add_resource(EventChannel::<Event>::with_capacity(2000));
         world.add_resource(EventChannel::<UiEvent>::with_capacity(40));
         world.add_resource(EventChannel::<TransEvent<T, StateEvent>>::with_capacity(2));
+        world.add_resource(EventChannel::<StateLifecycleEvent>::with_capacity(20));
         world.add_resource(Errors::default());
         world.add_resource(FrameLimiter::default());
+        world.add_resource(FrameStats::default());
         world.add_resource(Stopwatch::default());
         world.add_resource(Time::default());
+        world.add_resource(TimeDomains::default());
+        world.add_resource(Timers::default());
+        world.add_resource(EventChannel::<TimerEvent>::with_capacity(20));
+        world.add_resource(EventBus::default());
+        world.add_resource(SequenceRunner::default());
+        world.add_resource(EventChannel::<SequenceEvent>::with_capacity(20));
         world.add_resource(CallbackQueue::default());
+        world.add_resource(ShutdownHooks::default());
+        world.add_resource(LogLevel::default());
+        world.add_resource(LogBuffer::default());
 
         world.register::<Named>();
 
@@ -521,6 +693,9 @@ where
             initial_state,
             world,
             ignore_window_close: false,
+            headless: false,
+            handle_signals: false,
+            crash_reporter: None,
             phantom: PhantomData,
         })
     }
@@ -757,6 +932,88 @@ where
         self
     }
 
+    /// Tells the resulting application that it runs without a window or renderer if `headless`
+    /// is true.
+    ///
+    /// A headless application does not listen for window events, so it only stops when its
+    /// states quit or, when using
+    /// [`CoreApplication::run_frames`](struct.CoreApplication.html#method.run_frames), once the
+    /// given number of frames has passed. `FrameLimiter` and `Time` are updated as usual.
+    ///
+    /// Bundles that need a window, such as the `RenderBundle`, must not be added to the game data
+    /// of a headless application.
+    ///
+    /// # Parameters
+    ///
+    /// `headless`: Whether or not the application runs without a window.  False by default.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    /// Tells the resulting application to stop gracefully when the process receives SIGINT or
+    /// SIGTERM (Ctrl-C on Windows), if `handle` is true.
+    ///
+    /// Instead of killing the process, the signal stops all states and runs the
+    /// [`ShutdownHooks`](struct.ShutdownHooks.html), just like closing the window does.
+    /// Only one signal handler can be set per process.
+    ///
+    /// # Parameters
+    ///
+    /// `handle`: Whether or not termination signals should be handled.  False by default.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn handle_signals(mut self, handle: bool) -> Self {
+        self.handle_signals = handle;
+        self
+    }
+
+    /// Adds a function that runs when the application shuts down.
+    ///
+    /// See [`ShutdownHooks`](struct.ShutdownHooks.html) for the order in which hooks are run.
+    ///
+    /// # Parameters
+    ///
+    /// - `name`: Name of the hook, used for logging.
+    /// - `hook`: The function to run.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_shutdown_hook<N, F>(self, name: N, hook: F) -> Self
+    where
+        N: Into<String>,
+        F: FnMut(&mut World) + Send + Sync + 'static,
+    {
+        self.world.write_resource::<ShutdownHooks>().add(name, hook);
+        self
+    }
+
+    /// Tells the resulting application to write a crash report when a system or state panics
+    /// while running a frame.
+    ///
+    /// A panic hook is set when the application is built. It keeps calling the previously set
+    /// hook, so the panic is still printed to stderr.
+    ///
+    /// # Parameters
+    ///
+    /// `reporter`: Where to write reports and what to include in them, see
+    /// [`CrashReporter`](struct.CrashReporter.html).
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_crash_reporter(mut self, reporter: CrashReporter) -> Self {
+        self.crash_reporter = Some(reporter);
+        self
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -795,20 +1052,47 @@ where
         let mut reader = X::default();
         reader.setup(&mut self.world.res);
         let data = init.build(&mut self.world);
-        let event_reader_id = self
-            .world
-            .exec(|mut ev: Write<EventChannel<Event>>| ev.register_reader());
+        let event_reader_id = if self.headless {
+            info!("Running in headless mode");
+            None
+        } else {
+            Some(
+                self.world
+                    .exec(|mut ev: Write<EventChannel<Event>>| ev.register_reader()),
+            )
+        };
 
         let trans_reader_id = self
             .world
             .exec(|mut ev: Write<EventChannel<TransEvent<T, E>>>| ev.register_reader());
 
+        let signal = if self.handle_signals {
+            let signal = Arc::new(AtomicBool::new(false));
+            let handler_signal = signal.clone();
+            match ctrlc::set_handler(move || handler_signal.store(true, Ordering::Relaxed)) {
+                Ok(()) => Some(signal),
+                Err(err) => {
+                    warn!("Failed to set the termination signal handler: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(ref reporter) = self.crash_reporter {
+            reporter.install_hook();
+        }
+
         Ok(CoreApplication {
             world: self.world,
             states: StateMachine::new(self.initial_state),
             reader,
             events: Vec::new(),
             ignore_window_close: self.ignore_window_close,
+            signal,
+            crash_reporter: self.crash_reporter,
+            initialized: false,
             data,
             event_reader_id,
             trans_reader_id,
@@ -1,15 +1,54 @@
-use core::specs::World;
-use crossbeam_channel::{Receiver, Sender};
+use std::{
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+    time::Duration,
+};
+
+use core::{specs::World, timing::Time};
+use crash_report::panic_message;
+use crossbeam_channel::{self, Receiver, Sender};
 
 /// The type of a callback.
 /// This is meant to be created from within asynchonous functions (`Future` for example).
 /// See `CallbackQueue` for more details.
 pub type Callback = Box<Fn(&mut World) + Send>;
 
+type ScheduledFn = Box<FnMut(&mut World) + Send>;
+
+/// When a callback sent through a `CallbackSender` should run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Schedule {
+    /// On the next frame.
+    NextFrame,
+    /// On the first frame whose `Time::frame_number` is at least the given number.
+    Frame(u64),
+    /// Once the given real time has passed since the callback was received.
+    Delay(Duration),
+}
+
+struct ScheduledCallback {
+    callback: ScheduledFn,
+    schedule: Schedule,
+}
+
+/// A callback received by the `CallbackQueue` which is waiting for its frame or delay.
+struct PendingCallback {
+    callback: ScheduledFn,
+    frame: u64,
+    time: Duration,
+}
+
 /// A simple `Callback` queue.
 /// Using the `Sender` you can get using the `send_handle` method, you
 /// can add functions modifying `World` from an asynchronous context.
 /// Those callbacks will be ran sequentially without preserving ordering.
+///
+/// The `CallbackSender` you can get using the `sender` method additionally allows scheduling
+/// callbacks for a given frame or after a delay, and getting a value back from a callback.
+///
+/// A panicking callback does not crash the main loop: the panic is caught and logged, and the
+/// remaining callbacks still run.
 /// # Example
 /// ```rust,ignore
 /// // First, get a `Sender` handle.
@@ -24,6 +63,10 @@ pub type Callback = Box<Fn(&mut World) + Send>;
 pub struct CallbackQueue {
     sender: Sender<Callback>,
     pub(crate) receiver: Receiver<Callback>,
+    scheduled_sender: Sender<ScheduledCallback>,
+    scheduled_receiver: Receiver<ScheduledCallback>,
+    // Callbacks are `Send` but not `Sync`, so the mutex is needed for the queue to be a resource.
+    pending: Mutex<Vec<PendingCallback>>,
 }
 
 impl CallbackQueue {
@@ -36,11 +79,251 @@ impl CallbackQueue {
     pub fn send_handle(&self) -> Sender<Callback> {
         self.sender.clone()
     }
+
+    /// Creates a new handle that allows scheduling callbacks and getting values back from them.
+    pub fn sender(&self) -> CallbackSender {
+        CallbackSender {
+            sender: self.scheduled_sender.clone(),
+        }
+    }
+
+    /// Returns the number of callbacks waiting for their frame or delay.
+    pub fn num_pending(&self) -> usize {
+        self.pending
+            .lock()
+            .expect("Callback queue mutex poisoned")
+            .len()
+    }
+
+    /// Runs the callbacks that are due in the current frame.
+    pub(crate) fn run(world: &mut World) {
+        let (receiver, scheduled_receiver) = {
+            let queue = world.read_resource::<CallbackQueue>();
+            (queue.receiver.clone(), queue.scheduled_receiver.clone())
+        };
+
+        while let Ok(func) = receiver.try_recv() {
+            run_caught(|| func(world));
+        }
+
+        let (frame, now) = {
+            let time = world.read_resource::<Time>();
+            (time.frame_number(), time.absolute_real_time())
+        };
+        let mut pending = mem::replace(
+            &mut *world
+                .read_resource::<CallbackQueue>()
+                .pending
+                .lock()
+                .expect("Callback queue mutex poisoned"),
+            Vec::new(),
+        );
+        while let Ok(scheduled) = scheduled_receiver.try_recv() {
+            let (frame, time) = match scheduled.schedule {
+                Schedule::NextFrame => (0, now),
+                Schedule::Frame(frame) => (frame, now),
+                Schedule::Delay(delay) => (0, now + delay),
+            };
+            pending.push(PendingCallback {
+                callback: scheduled.callback,
+                frame,
+                time,
+            });
+        }
+
+        let (due, waiting): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|callback| callback.frame <= frame && callback.time <= now);
+        world
+            .read_resource::<CallbackQueue>()
+            .pending
+            .lock()
+            .expect("Callback queue mutex poisoned")
+            .extend(waiting);
+
+        for mut pending in due {
+            run_caught(|| (pending.callback)(world));
+        }
+    }
 }
 
 impl Default for CallbackQueue {
     fn default() -> Self {
         let (sender, receiver) = crossbeam_channel::unbounded();
-        CallbackQueue { sender, receiver }
+        let (scheduled_sender, scheduled_receiver) = crossbeam_channel::unbounded();
+        CallbackQueue {
+            sender,
+            receiver,
+            scheduled_sender,
+            scheduled_receiver,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// A handle to send callbacks to the `CallbackQueue` from other threads.
+///
+/// Unlike the `Sender` returned by `CallbackQueue::send_handle`, callbacks can be run on a given
+/// frame or after a delay, and can return a value to the sender.
+/// # Example
+/// ```rust,ignore
+/// let sender = world.read_resource::<CallbackQueue>().sender();
+/// thread::spawn(move || {
+///     let lobby = matchmaking.join();
+///     let entity = sender
+///         .request(move |world| world.create_entity().with(lobby).build())
+///         .wait();
+/// });
+/// ```
+#[derive(Clone)]
+pub struct CallbackSender {
+    sender: Sender<ScheduledCallback>,
+}
+
+impl CallbackSender {
+    /// Runs the callback on the next frame.
+    pub fn send<F>(&self, callback: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        self.schedule(Schedule::NextFrame, callback);
+    }
+
+    /// Runs the callback on the first frame whose `Time::frame_number` is at least `frame`.
+    pub fn send_at_frame<F>(&self, frame: u64, callback: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        self.schedule(Schedule::Frame(frame), callback);
+    }
+
+    /// Runs the callback on the first frame after `delay` has passed, measured with
+    /// `Time::absolute_real_time` from the frame the callback was received in.
+    pub fn send_after<F>(&self, delay: Duration, callback: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        self.schedule(Schedule::Delay(delay), callback);
+    }
+
+    /// Runs the callback on the next frame, and returns a handle to get the value it returns.
+    pub fn request<F, R>(&self, callback: F) -> CallbackReturn<R>
+    where
+        F: FnOnce(&mut World) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        self.send(move |world| {
+            // The sender may have dropped the `CallbackReturn`, in which case nobody waits for
+            // the value.
+            let _ = sender.send(callback(world));
+        });
+        CallbackReturn { receiver }
+    }
+
+    fn schedule<F>(&self, schedule: Schedule, callback: F)
+    where
+        F: FnOnce(&mut World) + Send + 'static,
+    {
+        let mut callback = Some(callback);
+        let scheduled = ScheduledCallback {
+            callback: Box::new(move |world: &mut World| {
+                if let Some(callback) = callback.take() {
+                    callback(world);
+                }
+            }),
+            schedule,
+        };
+        if self.sender.send(scheduled).is_err() {
+            warn!("Tried to send a callback to a `CallbackQueue` which has been dropped");
+        }
+    }
+}
+
+/// A handle to the value returned by a callback sent with `CallbackSender::request`.
+pub struct CallbackReturn<R> {
+    receiver: Receiver<R>,
+}
+
+impl<R> CallbackReturn<R> {
+    /// Returns the value if the callback has run, without blocking.
+    pub fn try_get(&self) -> Option<R> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Blocks until the callback has run and returns its value.
+    ///
+    /// Returns `None` if the callback panicked or the `CallbackQueue` was dropped before running
+    /// it. This must not be called from the thread running the application, as it would wait
+    /// forever.
+    pub fn wait(self) -> Option<R> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Runs a callback, logging instead of propagating any panic.
+///
+/// The `World` may be left in an inconsistent state by a panicking callback, but this is
+/// preferable to crashing the main loop because of a worker thread.
+fn run_caught<F: FnOnce()>(func: F) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(func)) {
+        error!("Callback panicked: {}", panic_message(&*payload));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.add_resource(CallbackQueue::default());
+        world.add_resource(0u32);
+        world
+    }
+
+    fn next_frame(world: &mut World) {
+        {
+            let mut time = world.write_resource::<Time>();
+            time.increment_frame_number();
+            time.set_delta_time(Duration::from_millis(10));
+        }
+        CallbackQueue::run(world);
+    }
+
+    #[test]
+    fn scheduled_callbacks_run_when_due() {
+        let mut world = world();
+        let sender = world.read_resource::<CallbackQueue>().sender();
+        sender.send_at_frame(3, |world| *world.write_resource::<u32>() += 1);
+        sender.send_after(Duration::from_millis(5), |world| {
+            *world.write_resource::<u32>() += 10
+        });
+
+        next_frame(&mut world);
+        assert_eq!(0, *world.read_resource::<u32>());
+        next_frame(&mut world);
+        assert_eq!(10, *world.read_resource::<u32>());
+        next_frame(&mut world);
+        assert_eq!(11, *world.read_resource::<u32>());
+        assert_eq!(0, world.read_resource::<CallbackQueue>().num_pending());
+    }
+
+    #[test]
+    fn panicking_callback_is_caught() {
+        let mut world = world();
+        let sender = world.read_resource::<CallbackQueue>().sender();
+        sender.send(|_| panic!("callback failure"));
+        let failed = sender.request(|_| -> u32 { panic!("callback failure") });
+        let value = sender.request(|world| {
+            *world.write_resource::<u32>() += 1;
+            42
+        });
+
+        next_frame(&mut world);
+        assert_eq!(1, *world.read_resource::<u32>());
+        assert_eq!(None, failed.wait());
+        assert_eq!(Some(42), value.try_get());
     }
 }
@@ -0,0 +1,297 @@
+//! Crash reports written when the main loop panics.
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use backtrace::Backtrace;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::Serialize;
+use shred::Resource;
+
+use {core::timing::Time, ecs::prelude::World, logger::LogBuffer};
+
+type ResourceSerializer = Box<Fn(&World) -> String>;
+
+/// The contents of a crash report file, serialized as RON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// The panic message.
+    pub message: String,
+    /// The source location of the panic, if known.
+    pub location: Option<String>,
+    /// The name of the thread which panicked, if it has one.
+    pub thread: Option<String>,
+    /// The `Time::frame_number` of the frame in which the panic happened.
+    pub frame_number: Option<u64>,
+    /// The names of the states on the stack, from the bottom to the active state.
+    pub states: Vec<String>,
+    /// The last log lines before the panic, oldest first.
+    pub log: Vec<String>,
+    /// The RON-serialized contents of the selected resources, by name.
+    pub resources: BTreeMap<String, String>,
+    /// The backtrace of the panicking thread.
+    pub backtrace: String,
+}
+
+/// Details of a panic recorded by the panic hook, which is the only place where the backtrace of
+/// the panicking thread is available.
+struct PanicRecord {
+    message: String,
+    location: Option<String>,
+    thread: Option<String>,
+    backtrace: String,
+}
+
+/// Writes a [`CrashReport`] file when a system or state panics during a frame.
+///
+/// The report contains the panic message and backtrace, the state stack, the last log lines kept
+/// by the `LogBuffer`, the current frame number and the contents of the selected resources. The
+/// panic is resumed once the report is written, so the application still aborts.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let game = Application::build("assets/", MainState)?
+///     .with_crash_reporter(
+///         CrashReporter::new("crash_reports")
+///             .with_log_lines(50)
+///             .with_resource::<Score, _>("score"),
+///     ).build(game_data)?;
+/// ```
+pub struct CrashReporter {
+    directory: PathBuf,
+    log_lines: usize,
+    resources: Vec<(String, ResourceSerializer)>,
+    panic: Arc<Mutex<Option<PanicRecord>>>,
+}
+
+impl CrashReporter {
+    /// Creates a new `CrashReporter` writing its reports to the given directory.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        CrashReporter {
+            directory: directory.into(),
+            log_lines: 100,
+            resources: Vec::new(),
+            panic: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sets the number of log lines included in a report. Defaults to 100.
+    pub fn with_log_lines(mut self, log_lines: usize) -> Self {
+        self.log_lines = log_lines;
+        self
+    }
+
+    /// Includes the RON-serialized contents of the resource `R` in reports, under the given name.
+    pub fn with_resource<R, N>(mut self, name: N) -> Self
+    where
+        R: Resource + Serialize,
+        N: Into<String>,
+    {
+        self.resources.push((
+            name.into(),
+            Box::new(|world: &World| match world.res.try_fetch::<R>() {
+                Some(resource) => to_string_pretty(&*resource, PrettyConfig::default())
+                    .unwrap_or_else(|err| format!("Failed to serialize resource: {}", err)),
+                None => "Resource not found".to_owned(),
+            }),
+        ));
+        self
+    }
+
+    /// Sets a panic hook recording the details of the last panic, then calling the previous
+    /// hook.
+    pub(crate) fn install_hook(&self) {
+        let record = self.panic.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Ok(mut record) = record.lock() {
+                *record = Some(PanicRecord {
+                    message: panic_message(info.payload()).to_owned(),
+                    location: info.location().map(|location| {
+                        format!(
+                            "{}:{}:{}",
+                            location.file(),
+                            location.line(),
+                            location.column()
+                        )
+                    }),
+                    thread: thread::current().name().map(str::to_owned),
+                    backtrace: format!("{:?}", Backtrace::new()),
+                });
+            }
+            previous(info);
+        }));
+    }
+
+    /// Forgets the recorded panic, so that panics which were caught before, e.g. by the
+    /// `CallbackQueue`, aren't reported for a later crash.
+    pub(crate) fn clear(&self) {
+        if let Ok(mut record) = self.panic.lock() {
+            *record = None;
+        }
+    }
+
+    /// Writes the report of the panic with the given payload, and returns the path of the file.
+    pub(crate) fn write(
+        &self,
+        world: &World,
+        states: Vec<&'static str>,
+        payload: &(Any + Send),
+    ) -> io::Result<PathBuf> {
+        let message = panic_message(payload);
+        let record = self
+            .panic
+            .lock()
+            .ok()
+            .and_then(|mut record| record.take())
+            // A record with another message is from a panic which was caught meanwhile.
+            .filter(|record| record.message == message)
+            .unwrap_or_else(|| PanicRecord {
+                message: message.to_owned(),
+                location: None,
+                thread: None,
+                backtrace: String::new(),
+            });
+
+        let report = CrashReport {
+            message: record.message,
+            location: record.location,
+            thread: record.thread,
+            frame_number: world.res.try_fetch::<Time>().map(|time| time.frame_number()),
+            states: states.into_iter().map(str::to_owned).collect(),
+            log: world
+                .res
+                .try_fetch::<LogBuffer>()
+                .map(|buffer| {
+                    buffer
+                        .last(self.log_lines)
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect()
+                }).unwrap_or_default(),
+            resources: self
+                .resources
+                .iter()
+                .map(|(name, serialize)| (name.clone(), serialize(world)))
+                .collect(),
+            backtrace: record.backtrace,
+        };
+        let contents = to_string_pretty(&report, PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() * 1000 + u64::from(duration.subsec_millis()))
+            .unwrap_or(0);
+        fs::create_dir_all(&self.directory)?;
+        // Never overwrite the report of another crash in the same millisecond.
+        let mut attempt = 0;
+        loop {
+            let name = match attempt {
+                0 => format!("crash_{}.ron", timestamp),
+                n => format!("crash_{}_{}.ron", timestamp, n),
+            };
+            let path = self.directory.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(path);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Returns the message of a panic payload, which is a `&str` or a `String` for panics raised by
+/// `panic!`.
+pub(crate) fn panic_message(payload: &(Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path, process};
+
+    use ron::de::from_str;
+
+    use logger;
+
+    use super::*;
+
+    fn read(path: &Path) -> CrashReport {
+        from_str(&fs::read_to_string(path).expect("Failed to read crash report"))
+            .expect("Failed to parse crash report")
+    }
+
+    fn record(message: &str) -> Option<PanicRecord> {
+        Some(PanicRecord {
+            message: message.to_owned(),
+            location: Some("src/game.rs:1:1".to_owned()),
+            thread: Some("main".to_owned()),
+            backtrace: "backtrace".to_owned(),
+        })
+    }
+
+    #[test]
+    fn writes_reports_without_overwriting() {
+        let directory = env::temp_dir().join(format!("amethyst_crash_test_{}", process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let reporter = CrashReporter::new(directory.clone()).with_resource::<u32, _>("score");
+
+        let mut world = World::new();
+        let mut time = Time::default();
+        time.increment_frame_number();
+        time.increment_frame_number();
+        world.add_resource(time);
+        world.add_resource(LogBuffer::default());
+        world.add_resource(42u32);
+        logger::buffer_record("loading level 3");
+
+        let payload: Box<Any + Send> = Box::new("boom");
+        *reporter.panic.lock().unwrap() = record("boom");
+        let first = reporter
+            .write(&world, vec!["menu", "game"], &*payload)
+            .expect("Failed to write crash report");
+        let report = read(&first);
+        assert_eq!("boom", report.message);
+        assert_eq!(Some("src/game.rs:1:1".to_owned()), report.location);
+        assert_eq!(Some(2), report.frame_number);
+        assert_eq!(vec!["menu", "game"], report.states);
+        assert!(report
+            .log
+            .iter()
+            .any(|line| line.ends_with("loading level 3")));
+        assert_eq!(Some(&"42".to_owned()), report.resources.get("score"));
+        assert_eq!("backtrace", report.backtrace);
+
+        // A cleared record, e.g. of a panic caught in an earlier frame, isn't reported.
+        *reporter.panic.lock().unwrap() = record("boom");
+        reporter.clear();
+        let second = reporter
+            .write(&world, vec!["menu"], &*payload)
+            .expect("Failed to write crash report");
+        assert_ne!(first, second);
+        assert_eq!(None, read(&second).location);
+        assert_eq!("boom", read(&first).message);
+
+        fs::remove_dir_all(directory).expect("Failed to remove crash reports");
+    }
+}
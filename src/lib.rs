@@ -78,15 +78,21 @@ pub extern crate amethyst_utils as utils;
 pub extern crate winit;
 
 extern crate amethyst_ui;
+extern crate backtrace;
 extern crate crossbeam_channel;
+extern crate ctrlc;
 #[macro_use]
 extern crate derivative;
 extern crate fern;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate amethyst_input;
 extern crate rayon;
+extern crate ron;
 extern crate rustc_version_runtime;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
@@ -94,13 +100,23 @@ pub use core::{shred, shrev, specs as ecs};
 
 pub use self::{
     app::{Application, ApplicationBuilder, CoreApplication},
-    callback_queue::{Callback, CallbackQueue},
+    callback_queue::{Callback, CallbackQueue, CallbackReturn, CallbackSender},
+    crash_report::{CrashReport, CrashReporter},
     error::{Error, Result},
     game_data::{DataInit, GameData, GameDataBuilder},
-    logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    loading_state::{LoadingState, LOADING_PROGRESS_ID},
+    logger::{
+        start_logger, LevelFilter as LogLevelFilter, LogBuffer, LogLevel, LogRecord, LogRotation,
+        Logger, LoggerConfig, StdoutLog,
+    },
+    sequence::{
+        Sequence, SequenceAction, SequenceAsset, SequenceCondition, SequenceEvent, SequenceId,
+        SequenceRunner, SequenceStep,
+    },
+    shutdown::{ShutdownHook, ShutdownHooks},
     state::{
-        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
-        TransEvent,
+        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateLifecycle,
+        StateLifecycleEvent, StateMachine, Trans, TransEvent, TransKind,
     },
     state_event::{StateEvent, StateEventReader},
 };
@@ -112,8 +128,12 @@ pub mod prelude;
 
 mod app;
 mod callback_queue;
+mod crash_report;
 mod error;
 mod game_data;
+mod loading_state;
 mod logger;
+mod sequence;
+mod shutdown;
 mod state;
 mod state_event;
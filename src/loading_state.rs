@@ -0,0 +1,247 @@
+//! A state which loads assets before switching to another state.
+
+use {
+    assets::{AssetErrorMeta, Completion, ProgressCounter},
+    core::{ParentHierarchy, SceneGraph},
+    ecs::prelude::{Entity, World, WriteStorage},
+    ui::{UiCreator, UiFinder, UiText},
+    GameData, SimpleState, SimpleTrans, StateData, Trans,
+};
+
+/// The `UiTransform` id of the `UiText` in a loading screen which displays the loading progress.
+pub const LOADING_PROGRESS_ID: &str = "loading_progress";
+
+type LoadFn = Box<FnMut(&mut World, &mut ProgressCounter)>;
+type FailureFn = Box<FnMut(&mut World, Vec<AssetErrorMeta>)>;
+
+/// A `State` which issues a set of asset load requests and switches to the next state once all of
+/// them completed.
+///
+/// While loading, an optional loading screen UI prefab is displayed. If it contains a `UiText`
+/// with the id `"loading_progress"`, its text is updated with the number of loaded assets.
+/// Using a loading screen requires the `UiBundle` to be added to the game data.
+///
+/// If any asset fails to load, the failures are passed to the failure handler, and the loading
+/// state is popped off the stack. Without a failure handler, the failures are logged.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let loading = LoadingState::new(GameplayState::default())
+///     .with_loading_screen("ui/loading.ron")
+///     .with_load(|world, progress| {
+///         let handle = world.exec(|loader: PrefabLoader<MyPrefabData>| {
+///             loader.load("prefab/level.ron", RonFormat, (), progress)
+///         });
+///         world.add_resource(LevelPrefab(handle));
+///     });
+///
+/// Trans::Push(Box::new(loading))
+/// ```
+pub struct LoadingState<S> {
+    next_state: Option<S>,
+    loads: Vec<LoadFn>,
+    loading_screen: Option<String>,
+    on_failure: Option<FailureFn>,
+    progress: ProgressCounter,
+    screen_entity: Option<Entity>,
+}
+
+impl<S> LoadingState<S> {
+    /// Creates a new `LoadingState`, which switches to `next_state` once loading is complete.
+    pub fn new(next_state: S) -> Self {
+        LoadingState {
+            next_state: Some(next_state),
+            loads: Vec::new(),
+            loading_screen: None,
+            on_failure: None,
+            progress: ProgressCounter::new(),
+            screen_entity: None,
+        }
+    }
+
+    /// Adds a function which issues asset load requests when the loading state starts.
+    ///
+    /// All loads must use the given `ProgressCounter` to be waited for.
+    pub fn with_load<F>(mut self, load: F) -> Self
+    where
+        F: FnOnce(&mut World, &mut ProgressCounter) + 'static,
+    {
+        let mut load = Some(load);
+        self.loads
+            .push(Box::new(move |world: &mut World, progress: &mut ProgressCounter| {
+                if let Some(load) = load.take() {
+                    load(world, progress);
+                }
+            }));
+        self
+    }
+
+    /// Sets the name of the UI prefab to display while loading.
+    pub fn with_loading_screen<N>(mut self, prefab: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.loading_screen = Some(prefab.into());
+        self
+    }
+
+    /// Sets the function which is given the assets that failed to load.
+    pub fn with_failure_handler<F>(mut self, on_failure: F) -> Self
+    where
+        F: FnMut(&mut World, Vec<AssetErrorMeta>) + 'static,
+    {
+        self.on_failure = Some(Box::new(on_failure));
+        self
+    }
+
+    fn show_progress(&self, world: &mut World) {
+        let text = format!(
+            "{}/{}",
+            self.progress.num_finished(),
+            self.progress.num_assets()
+        );
+        world.exec(|(finder, mut texts): (UiFinder, WriteStorage<UiText>)| {
+            if let Some(ui_text) = finder
+                .find(LOADING_PROGRESS_ID)
+                .and_then(|entity| texts.get_mut(entity))
+            {
+                ui_text.text = text;
+            }
+        });
+    }
+}
+
+impl<'a, 'b, S> SimpleState<'a, 'b> for LoadingState<S>
+where
+    S: SimpleState<'a, 'b> + 'static,
+{
+    fn on_start(&mut self, data: StateData<GameData>) {
+        if let Some(ref loading_screen) = self.loading_screen {
+            let loading_screen = loading_screen.clone();
+            self.screen_entity = Some(
+                data.world
+                    .exec(|mut creator: UiCreator| creator.create(loading_screen, ())),
+            );
+        }
+
+        for mut load in self.loads.drain(..) {
+            load(data.world, &mut self.progress);
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<GameData>) {
+        if let Some(entity) = self.screen_entity.take() {
+            let deleted = data
+                .world
+                .read_resource::<ParentHierarchy>()
+                .delete_with_descendants(entity, &data.world.entities());
+            if let Err(err) = deleted {
+                warn!("Failed to delete the loading screen: {:?}", err);
+            }
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+        if self.screen_entity.is_some() {
+            self.show_progress(data.world);
+        }
+
+        match self.progress.complete() {
+            Completion::Loading => Trans::None,
+            Completion::Complete => match self.next_state.take() {
+                Some(next_state) => Trans::Switch(Box::new(next_state)),
+                None => Trans::Pop,
+            },
+            Completion::Failed => {
+                let errors = self.progress.errors();
+                match self.on_failure {
+                    Some(ref mut on_failure) => on_failure(data.world, errors),
+                    None => error!(
+                        "{} asset(s) failed to load: {:?}",
+                        errors.len(),
+                        errors
+                            .iter()
+                            .map(|meta| meta.asset_name.as_str())
+                            .collect::<Vec<_>>()
+                    ),
+                }
+                Trans::Pop
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use assets::{Progress, Tracker};
+    use ecs::prelude::DispatcherBuilder;
+    use {StateEvent, StateMachine};
+
+    use super::*;
+
+    struct Named(&'static str);
+
+    impl<'a, 'b> SimpleState<'a, 'b> for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    /// Loads one asset, which succeeds or fails right away.
+    fn load(succeed: bool) -> impl FnOnce(&mut World, &mut ProgressCounter) {
+        move |_: &mut World, mut progress: &mut ProgressCounter| {
+            progress.add_assets(1);
+            let tracker = Box::new(progress.create_tracker());
+            if succeed {
+                tracker.success();
+            } else {
+                tracker.fail(0, "Asset", "missing.ron".to_owned(), "Not found".into());
+            }
+        }
+    }
+
+    fn run(loading: LoadingState<Named>) -> Vec<&'static str> {
+        let mut world = World::new();
+        let mut game_data = GameData::new(DispatcherBuilder::new().build());
+
+        let mut sm = StateMachine::<_, StateEvent>::new(Named("menu"));
+        sm.start(StateData::new(&mut world, &mut game_data))
+            .unwrap();
+        sm.transition(
+            Trans::Push(Box::new(loading)),
+            StateData::new(&mut world, &mut game_data),
+        );
+        sm.update(StateData::new(&mut world, &mut game_data));
+        sm.state_names()
+    }
+
+    #[test]
+    fn switches_to_next_state_once_loaded() {
+        let loading = LoadingState::new(Named("game")).with_load(load(true));
+        assert_eq!(vec!["menu", "game"], run(loading));
+    }
+
+    #[test]
+    fn failure_handler_gets_errors_then_pops() {
+        let failures = Rc::new(Cell::new(0));
+        let handled = failures.clone();
+        let loading = LoadingState::new(Named("game"))
+            .with_load(load(true))
+            .with_load(load(false))
+            .with_failure_handler(move |_, errors| {
+                assert_eq!("missing.ron", errors[0].asset_name);
+                handled.set(handled.get() + errors.len());
+            });
+        assert_eq!(vec!["menu"], run(loading));
+        assert_eq!(1, failures.get());
+    }
+
+    #[test]
+    fn pops_on_failure_without_handler() {
+        let loading = LoadingState::new(Named("game")).with_load(load(false));
+        assert_eq!(vec!["menu"], run(loading));
+    }
+}
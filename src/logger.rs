@@ -1,8 +1,23 @@
 pub use log::LevelFilter;
 
-use std::{env, io, path::PathBuf, str::FromStr};
+use std::{
+    collections::{HashMap, VecDeque},
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
 
 use fern;
+use log::{self, Level, Log, Metadata, Record};
+
+lazy_static! {
+    static ref FILTERS: RwLock<LevelFilters> = RwLock::new(LevelFilters::default());
+    static ref RECORDS: Mutex<RecordBuffer> = Mutex::new(RecordBuffer::default());
+}
 
 /// An enum that contains options for logging to the terminal.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -15,6 +30,37 @@ pub enum StdoutLog {
     Colored,
 }
 
+/// An enum that contains options for rotating the log file.
+///
+/// When the log file is rotated, it is renamed to `<log_file>.1`, older log files are renamed from
+/// `<log_file>.N` to `<log_file>.N+1`, and the oldest ones are deleted so that at most `keep`
+/// rotated files remain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LogRotation {
+    /// Never rotates the log file.
+    Never,
+    /// Rotates the log file before it grows larger than `max_bytes`.
+    Size {
+        /// The maximum size of the log file in bytes.
+        max_bytes: u64,
+        /// The number of rotated log files to keep.
+        keep: usize,
+    },
+    /// Rotates the log file once `seconds` have passed since it was opened.
+    Interval {
+        /// The number of seconds between rotations.
+        seconds: u64,
+        /// The number of rotated log files to keep.
+        keep: usize,
+    },
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation::Never
+    }
+}
+
 /// Logger configuration object.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct LoggerConfig {
@@ -24,16 +70,35 @@ pub struct LoggerConfig {
     pub level_filter: LevelFilter,
     /// If set, enables logging to file at the given path.
     pub log_file: Option<PathBuf>,
+    /// Determines when the log file is rotated.
+    #[serde(default)]
+    pub log_rotation: LogRotation,
+    /// Overrides the level filter for individual modules, e.g. `{"gfx_device_gl": Warn}`.
+    ///
+    /// An override applies to the module and all of its submodules.
+    #[serde(default)]
+    pub module_levels: HashMap<String, LevelFilter>,
+    /// The number of recent records kept in memory and readable through the `LogBuffer` resource.
+    /// Setting it to `0` disables the buffer.
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
     /// If set, allows the config values to be overriden via the corresponding environmental variables.
     pub allow_env_override: bool,
 }
 
+fn default_buffer_size() -> usize {
+    256
+}
+
 impl Default for LoggerConfig {
     fn default() -> LoggerConfig {
         LoggerConfig {
             stdout: StdoutLog::Colored,
             level_filter: LevelFilter::Debug,
             log_file: None,
+            log_rotation: LogRotation::Never,
+            module_levels: HashMap::new(),
+            buffer_size: default_buffer_size(),
             allow_env_override: true,
         }
     }
@@ -51,6 +116,8 @@ impl Default for LoggerConfig {
 /// ```
 pub struct Logger {
     dispatch: fern::Dispatch,
+    filters: LevelFilters,
+    buffer_size: usize,
 }
 
 impl Logger {
@@ -63,7 +130,11 @@ impl Logger {
                 message = message,
             ))
         });
-        Logger { dispatch }
+        Logger {
+            dispatch,
+            filters: LevelFilters::default(),
+            buffer_size: 0,
+        }
     }
 
     /// Create a new Logger from [`LoggerConfig`]
@@ -73,7 +144,9 @@ impl Logger {
         }
 
         let mut logger = Logger::new();
-        logger.dispatch = logger.dispatch.level(config.level_filter);
+        logger.filters.global = config.level_filter;
+        logger.filters.modules = config.module_levels;
+        logger.buffer_size = config.buffer_size;
 
         match config.stdout {
             StdoutLog::Plain => logger.dispatch = logger.dispatch.chain(io::stdout()),
@@ -86,9 +159,22 @@ impl Logger {
         }
 
         if let Some(path) = config.log_file {
-            match fern::log_file(path) {
-                Ok(log_file) => logger.dispatch = logger.dispatch.chain(log_file),
-                Err(_) => eprintln!("Unable to access the log file, as such it will not be used"),
+            match config.log_rotation {
+                LogRotation::Never => match fern::log_file(path) {
+                    Ok(log_file) => logger.dispatch = logger.dispatch.chain(log_file),
+                    Err(_) => {
+                        eprintln!("Unable to access the log file, as such it will not be used")
+                    }
+                },
+                rotation => match RotatingFile::open(path, rotation) {
+                    Ok(log_file) => {
+                        let log_file: Box<Write + Send> = Box::new(log_file);
+                        logger.dispatch = logger.dispatch.chain(log_file);
+                    }
+                    Err(_) => {
+                        eprintln!("Unable to access the log file, as such it will not be used")
+                    }
+                },
             }
         }
 
@@ -101,16 +187,357 @@ impl Logger {
         module: T,
         level: LevelFilter,
     ) -> Self {
-        self.dispatch = self.dispatch.level_for(module, level);
+        self.filters
+            .modules
+            .insert(module.into().into_owned(), level);
         self
     }
 
     /// Starts [`Logger`] by consuming it.
     pub fn start(self) {
-        self.dispatch.apply().unwrap_or_else(|_| {
-            debug!("Global logger already set, default Amethyst logger will not be used")
+        let Logger {
+            dispatch,
+            filters,
+            buffer_size,
+        } = self;
+
+        // Filtering is done against the shared filters so that `LogLevel` can change them later.
+        let mut root = fern::Dispatch::new()
+            .level(LevelFilter::Trace)
+            .filter(|metadata| read_filters().enabled(metadata))
+            .chain(dispatch);
+        if buffer_size > 0 {
+            let buffer: Box<Log> = Box::new(BufferLogger);
+            root = root.chain(buffer);
+        }
+
+        match root.apply() {
+            Ok(()) => {
+                lock_records().set_capacity(buffer_size);
+                let mut shared = write_filters();
+                *shared = filters;
+                log::set_max_level(shared.max());
+            }
+            Err(_) => {
+                debug!("Global logger already set, default Amethyst logger will not be used")
+            }
+        }
+    }
+}
+
+/// A resource to read and change the level filters of the Amethyst logger at runtime.
+///
+/// The filters start out as configured in the [`LoggerConfig`] and [`Logger::level_for`].
+/// Changing them has no effect on records if another global logger than the one of Amethyst
+/// is used.
+///
+/// # Example
+/// ```rust,ignore
+/// let mut log_level = world.write_resource::<LogLevel>();
+/// log_level.set_level_for("amethyst_assets", LevelFilter::Trace);
+/// ```
+#[derive(Default, Debug)]
+pub struct LogLevel {
+    _private: (),
+}
+
+impl LogLevel {
+    /// Returns the overarching level filter.
+    pub fn level(&self) -> LevelFilter {
+        read_filters().global
+    }
+
+    /// Sets the overarching level filter.
+    pub fn set_level(&mut self, level: LevelFilter) {
+        let mut filters = write_filters();
+        filters.global = level;
+        log::set_max_level(filters.max());
+    }
+
+    /// Returns the level filter used for records of the given module.
+    pub fn level_for(&self, module: &str) -> LevelFilter {
+        read_filters().level_for(module)
+    }
+
+    /// Overrides the level filter for the given module and its submodules.
+    pub fn set_level_for<T: Into<String>>(&mut self, module: T, level: LevelFilter) {
+        let mut filters = write_filters();
+        filters.modules.insert(module.into(), level);
+        log::set_max_level(filters.max());
+    }
+
+    /// Removes the override for the given module, so the overarching level filter applies again.
+    pub fn reset_level_for(&mut self, module: &str) {
+        let mut filters = write_filters();
+        filters.modules.remove(module);
+        log::set_max_level(filters.max());
+    }
+}
+
+/// A record kept in the in-memory buffer of the Amethyst logger.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record, usually the module it was logged from.
+    pub target: String,
+    /// The message of the record.
+    pub message: String,
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}][{}] {}", self.level, self.target, self.message)
+    }
+}
+
+/// A resource giving access to the most recent records logged through the Amethyst logger,
+/// e.g. for an in-game console or a crash report.
+///
+/// The number of records kept is set by [`LoggerConfig::buffer_size`].
+#[derive(Default, Debug)]
+pub struct LogBuffer {
+    _private: (),
+}
+
+impl LogBuffer {
+    /// Returns the maximum number of records kept.
+    pub fn capacity(&self) -> usize {
+        lock_records().capacity
+    }
+
+    /// Returns all records in the buffer, oldest first.
+    pub fn records(&self) -> Vec<LogRecord> {
+        lock_records().records.iter().cloned().collect()
+    }
+
+    /// Returns the last `count` records in the buffer, oldest first.
+    pub fn last(&self, count: usize) -> Vec<LogRecord> {
+        let records = lock_records();
+        let skip = records.records.len().saturating_sub(count);
+        records.records.iter().skip(skip).cloned().collect()
+    }
+
+    /// Removes all records from the buffer.
+    pub fn clear(&mut self) {
+        lock_records().records.clear();
+    }
+}
+
+struct LevelFilters {
+    global: LevelFilter,
+    modules: HashMap<String, LevelFilter>,
+}
+
+impl Default for LevelFilters {
+    fn default() -> Self {
+        LevelFilters {
+            global: LevelFilter::Debug,
+            modules: HashMap::new(),
+        }
+    }
+}
+
+impl LevelFilters {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    /// Returns the level of the longest module path matching the target.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target.starts_with(module.as_str())
+                    && (target.len() == module.len() || target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.global)
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .values()
+            .cloned()
+            .fold(self.global, ::std::cmp::max)
+    }
+}
+
+fn read_filters() -> RwLockReadGuard<'static, LevelFilters> {
+    FILTERS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write_filters() -> RwLockWriteGuard<'static, LevelFilters> {
+    FILTERS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Default)]
+struct RecordBuffer {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+}
+
+impl RecordBuffer {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.records.len() > capacity {
+            self.records.pop_front();
+        }
+    }
+
+    fn push(&mut self, record: LogRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+fn lock_records() -> MutexGuard<'static, RecordBuffer> {
+    RECORDS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Adds a record to the shared record buffer, for the tests of modules reading the `LogBuffer`.
+#[cfg(test)]
+pub(crate) fn buffer_record(message: &str) {
+    let mut records = lock_records();
+    if records.capacity == 0 {
+        records.set_capacity(100);
+    }
+    records.push(LogRecord {
+        level: Level::Info,
+        target: "test".to_owned(),
+        message: message.to_owned(),
+    });
+}
+
+/// Stores the records it receives in the shared record buffer.
+struct BufferLogger;
+
+impl Log for BufferLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        lock_records().push(LogRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
         });
     }
+
+    fn flush(&self) {}
+}
+
+/// A log file which is rotated according to a `LogRotation`.
+struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    file: File,
+    size: u64,
+    opened: Instant,
+    line_start: bool,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, rotation: LogRotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            rotation,
+            file,
+            size,
+            opened: Instant::now(),
+            line_start: true,
+        })
+    }
+
+    fn should_rotate(&self, len: usize) -> bool {
+        match self.rotation {
+            LogRotation::Never => false,
+            LogRotation::Size { max_bytes, .. } => {
+                self.size > 0 && self.size + len as u64 > max_bytes
+            }
+            LogRotation::Interval { seconds, .. } => {
+                self.opened.elapsed() >= Duration::from_secs(seconds)
+            }
+        }
+    }
+
+    fn keep(&self) -> usize {
+        match self.rotation {
+            LogRotation::Never => 0,
+            LogRotation::Size { keep, .. } | LogRotation::Interval { keep, .. } => keep,
+        }
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let keep = self.keep();
+        if keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(keep);
+            if oldest.exists() {
+                fs::remove_file(oldest)?;
+            }
+            for index in (1..keep).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Records may be written in several parts, only rotate between lines.
+        if self.line_start && self.should_rotate(buf.len()) {
+            if let Err(err) = self.rotate() {
+                eprintln!("Unable to rotate the log file: {}", err);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
 /// Starts a basic logger outputting to stdout with color on supported platforms, and/or to file.
@@ -158,3 +585,56 @@ fn colored_stdout(color_config: fern::colors::ColoredLevelConfig) -> fern::Dispa
             ))
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_levels_apply_to_submodules() {
+        let mut filters = LevelFilters {
+            global: LevelFilter::Warn,
+            modules: HashMap::new(),
+        };
+        filters
+            .modules
+            .insert("amethyst_assets".to_owned(), LevelFilter::Debug);
+        filters
+            .modules
+            .insert("amethyst_assets::source".to_owned(), LevelFilter::Trace);
+
+        assert_eq!(LevelFilter::Debug, filters.level_for("amethyst_assets"));
+        assert_eq!(
+            LevelFilter::Debug,
+            filters.level_for("amethyst_assets::loader")
+        );
+        assert_eq!(
+            LevelFilter::Trace,
+            filters.level_for("amethyst_assets::source::dir")
+        );
+        assert_eq!(
+            LevelFilter::Warn,
+            filters.level_for("amethyst_assets_extra")
+        );
+        assert_eq!(LevelFilter::Trace, filters.max());
+    }
+
+    #[test]
+    fn record_buffer_keeps_latest_records() {
+        let mut buffer = RecordBuffer::default();
+        buffer.set_capacity(2);
+        for message in &["a", "b", "c"] {
+            buffer.push(LogRecord {
+                level: Level::Info,
+                target: "test".to_owned(),
+                message: message.to_string(),
+            });
+        }
+        let messages = buffer
+            .records
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["b", "c"], messages);
+    }
+}
@@ -2,7 +2,7 @@
 
 pub use {
     app::{Application, ApplicationBuilder, CoreApplication},
-    callback_queue::{Callback, CallbackQueue},
+    callback_queue::{Callback, CallbackQueue, CallbackReturn, CallbackSender},
     config::Config,
     core::WithNamed,
     ecs::prelude::{Builder, World},
@@ -0,0 +1,661 @@
+//! Scripted sequences of steps, like cutscenes and tutorials, stepped by the main loop.
+
+use std::{collections::HashMap, mem};
+
+use {
+    assets::{Asset, Handle, ProcessingState, Result as AssetResult},
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+    },
+    ecs::prelude::{Entity, VecStorage, World},
+};
+
+/// A function run by a sequence step.
+pub type SequenceAction = Box<FnMut(&mut World) + Send + Sync>;
+
+/// A condition a sequence step waits for.
+pub type SequenceCondition = Box<FnMut(&World) -> bool + Send + Sync>;
+
+/// Identifies a sequence started by the `SequenceRunner`, so that it can be cancelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SequenceId(u64);
+
+/// Event sent to the `EventChannel<SequenceEvent>` resource when a sequence stops running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// The sequence went through all of its steps.
+    Finished(SequenceId),
+    /// The sequence was cancelled, or the entity it was started for was deleted.
+    Cancelled(SequenceId),
+}
+
+/// Waits for an event of a given type, registering its reader when the step starts so that only
+/// events sent afterwards are taken into account.
+trait EventWait: Send + Sync {
+    fn start(&mut self, world: &mut World);
+
+    fn poll(&mut self, world: &World) -> bool;
+}
+
+struct EventWaiter<E: 'static, F> {
+    reader: Option<ReaderId<E>>,
+    predicate: F,
+}
+
+impl<E, F> EventWait for EventWaiter<E, F>
+where
+    E: Send + Sync + 'static,
+    F: FnMut(&E) -> bool + Send + Sync,
+{
+    fn start(&mut self, world: &mut World) {
+        self.reader = Some(
+            world
+                .res
+                .entry::<EventChannel<E>>()
+                .or_insert_with(EventChannel::new)
+                .register_reader(),
+        );
+    }
+
+    fn poll(&mut self, world: &World) -> bool {
+        let received = {
+            let predicate = &mut self.predicate;
+            let reader = self
+                .reader
+                .as_mut()
+                .expect("Event wait polled before it started");
+            world
+                .read_resource::<EventChannel<E>>()
+                .read(reader)
+                .any(|event| predicate(event))
+        };
+        if received {
+            // Stop holding back the events of the channel.
+            self.reader = None;
+        }
+        received
+    }
+}
+
+enum Step {
+    Wait(f32),
+    WaitUntil(SequenceCondition),
+    WaitFor(String),
+    WaitEvent(Box<EventWait>),
+    Run(SequenceAction),
+    Action(String),
+    Parallel(Vec<Sequence>),
+    Repeat {
+        count: Option<u32>,
+        done: u32,
+        sequence: Sequence,
+    },
+}
+
+/// The named actions and conditions of the `SequenceRunner`, used by sequences loaded from assets.
+#[derive(Default)]
+struct Registry {
+    actions: HashMap<String, SequenceAction>,
+    conditions: HashMap<String, SequenceCondition>,
+}
+
+impl Registry {
+    fn extend(&mut self, other: Registry) {
+        self.actions.extend(other.actions);
+        self.conditions.extend(other.conditions);
+    }
+}
+
+/// A chain of steps run one after the other by the `SequenceRunner`.
+///
+/// Steps run as soon as the previous one is done, so several steps may run in the same frame, while
+/// waiting steps make the sequence continue in a later frame.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let intro = Sequence::new()
+///     .wait(2.0)
+///     .run(|world| world.write_resource::<CameraTarget>().set(door))
+///     .action("show_welcome_text")
+///     .wait_for_event(|event: &InputEvent<String>| match event {
+///         InputEvent::ActionPressed(action) => action == "confirm",
+///         _ => false,
+///     }).run(|world| world.write_resource::<CameraTarget>().reset());
+/// world.write_resource::<SequenceRunner>().start(intro);
+/// ```
+#[derive(Default)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    current: usize,
+    elapsed: f32,
+    started: bool,
+}
+
+impl Sequence {
+    /// Creates a new, empty sequence.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Waits for the given number of seconds of game time, as given by `Time::delta_seconds`.
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push(Step::Wait(seconds));
+        self
+    }
+
+    /// Waits until the condition is true, checking it once per frame.
+    pub fn wait_until<F>(mut self, condition: F) -> Self
+    where
+        F: FnMut(&World) -> bool + Send + Sync + 'static,
+    {
+        self.steps.push(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+
+    /// Waits until the condition registered with the given name in the `SequenceRunner` is true.
+    pub fn wait_for<N: Into<String>>(mut self, condition: N) -> Self {
+        self.steps.push(Step::WaitFor(condition.into()));
+        self
+    }
+
+    /// Waits until an event of type `E` matching the predicate is sent to the `EventChannel<E>`
+    /// resource, after the step started.
+    pub fn wait_for_event<E, F>(mut self, predicate: F) -> Self
+    where
+        E: Send + Sync + 'static,
+        F: FnMut(&E) -> bool + Send + Sync + 'static,
+    {
+        self.steps.push(Step::WaitEvent(Box::new(EventWaiter {
+            reader: None,
+            predicate,
+        })));
+        self
+    }
+
+    /// Runs a function on the world.
+    pub fn run<F>(mut self, action: F) -> Self
+    where
+        F: FnMut(&mut World) + Send + Sync + 'static,
+    {
+        self.steps.push(Step::Run(Box::new(action)));
+        self
+    }
+
+    /// Runs the action registered with the given name in the `SequenceRunner`.
+    pub fn action<N: Into<String>>(mut self, action: N) -> Self {
+        self.steps.push(Step::Action(action.into()));
+        self
+    }
+
+    /// Runs the sequences side by side, and continues once all of them are done.
+    pub fn parallel(mut self, sequences: Vec<Sequence>) -> Self {
+        self.steps.push(Step::Parallel(sequences));
+        self
+    }
+
+    /// Runs the sequence the given number of times, or forever if `count` is `None`. A count of
+    /// zero skips the sequence.
+    ///
+    /// A repetition starts at most once per frame, so repeating a sequence without waiting steps
+    /// doesn't block the main loop.
+    pub fn repeat(mut self, count: Option<u32>, sequence: Sequence) -> Self {
+        self.steps.push(Step::Repeat {
+            count,
+            done: 0,
+            sequence,
+        });
+        self
+    }
+
+    /// Returns true once all steps are done.
+    pub fn is_finished(&self) -> bool {
+        self.current >= self.steps.len()
+    }
+
+    /// Goes back to the first step.
+    fn reset(&mut self) {
+        self.current = 0;
+        self.elapsed = 0.;
+        self.started = false;
+        for step in &mut self.steps {
+            match step {
+                Step::Parallel(sequences) => sequences.iter_mut().for_each(Sequence::reset),
+                Step::Repeat { done, sequence, .. } => {
+                    *done = 0;
+                    sequence.reset();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs the steps which are ready, and returns true once all steps are done.
+    fn step(&mut self, world: &mut World, mut delta: f32, registry: &mut Registry) -> bool {
+        while let Some(step) = self.steps.get_mut(self.current) {
+            let done = match step {
+                Step::Wait(duration) => {
+                    self.elapsed += delta;
+                    if self.elapsed < *duration {
+                        return false;
+                    }
+                    // Carry the remaining time over to the next waiting step.
+                    delta = self.elapsed - *duration;
+                    true
+                }
+                Step::WaitUntil(condition) => condition(world),
+                Step::WaitFor(name) => match registry.conditions.get_mut(name) {
+                    Some(condition) => condition(world),
+                    None => {
+                        error!(
+                            "Sequence condition `{}` is not registered, skipping it",
+                            name
+                        );
+                        true
+                    }
+                },
+                Step::WaitEvent(waiter) => {
+                    if !self.started {
+                        waiter.start(world);
+                        self.started = true;
+                    }
+                    waiter.poll(world)
+                }
+                Step::Run(action) => {
+                    action(world);
+                    true
+                }
+                Step::Action(name) => {
+                    match registry.actions.get_mut(name) {
+                        Some(action) => action(world),
+                        None => error!("Sequence action `{}` is not registered, skipping it", name),
+                    }
+                    true
+                }
+                Step::Parallel(sequences) => {
+                    let mut finished = true;
+                    for sequence in sequences.iter_mut() {
+                        finished &= sequence.step(world, delta, registry);
+                    }
+                    delta = 0.;
+                    finished
+                }
+                Step::Repeat {
+                    count,
+                    done,
+                    sequence,
+                } => {
+                    let finished = if count.map_or(false, |count| *done >= count) {
+                        // A count of zero skips the sequence.
+                        true
+                    } else if sequence.step(world, delta, registry) {
+                        *done += 1;
+                        if count.map_or(false, |count| *done >= count) {
+                            true
+                        } else {
+                            sequence.reset();
+                            false
+                        }
+                    } else {
+                        false
+                    };
+                    delta = 0.;
+                    finished
+                }
+            };
+            if !done {
+                return false;
+            }
+            self.current += 1;
+            self.elapsed = 0.;
+            self.started = false;
+        }
+        true
+    }
+}
+
+impl<'a> From<&'a [SequenceStep]> for Sequence {
+    fn from(steps: &'a [SequenceStep]) -> Self {
+        steps
+            .iter()
+            .fold(Sequence::new(), |sequence, step| match step {
+                SequenceStep::Wait(seconds) => sequence.wait(*seconds),
+                SequenceStep::Action(name) => sequence.action(name.clone()),
+                SequenceStep::WaitFor(name) => sequence.wait_for(name.clone()),
+                SequenceStep::Parallel(sequences) => sequence.parallel(
+                    sequences
+                        .iter()
+                        .map(|steps| Sequence::from(&steps[..]))
+                        .collect(),
+                ),
+                SequenceStep::Repeat(count, steps) => {
+                    sequence.repeat(*count, Sequence::from(&steps[..]))
+                }
+            })
+    }
+}
+
+impl<'a> From<&'a SequenceAsset> for Sequence {
+    fn from(asset: &'a SequenceAsset) -> Self {
+        Sequence::from(&asset.steps[..])
+    }
+}
+
+/// A step of a `SequenceAsset`.
+///
+/// Actions and conditions are referred to by the name they are registered with in the
+/// `SequenceRunner`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SequenceStep {
+    /// Waits for the given number of seconds.
+    Wait(f32),
+    /// Runs the named action.
+    Action(String),
+    /// Waits until the named condition is true.
+    WaitFor(String),
+    /// Runs the sequences side by side.
+    Parallel(Vec<Vec<SequenceStep>>),
+    /// Runs the steps the given number of times, or forever.
+    Repeat(Option<u32>, Vec<SequenceStep>),
+}
+
+/// A sequence authored as an asset, usually loaded from RON with the `RonFormat`.
+///
+/// The `Processor<SequenceAsset>` system needs to be added to the dispatcher to load it, and a
+/// `Sequence` can then be created from the asset with `Sequence::from(&asset)`.
+///
+/// # Example
+///
+/// ```ron
+/// (
+///     steps: [
+///         Wait(2.0),
+///         Action("move_camera_to_door"),
+///         Parallel([
+///             [Action("open_door"), Wait(1.0)],
+///             [Action("show_welcome_text")],
+///         ]),
+///         WaitFor("confirm_pressed"),
+///         Repeat(Some(3), [Action("blink_arrow"), Wait(0.5)]),
+///     ],
+/// )
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SequenceAsset {
+    /// The steps of the sequence.
+    pub steps: Vec<SequenceStep>,
+}
+
+impl Asset for SequenceAsset {
+    const NAME: &'static str = "amethyst::SequenceAsset";
+    type Data = SequenceAsset;
+    type HandleStorage = VecStorage<Handle<SequenceAsset>>;
+}
+
+impl Into<AssetResult<ProcessingState<SequenceAsset>>> for SequenceAsset {
+    fn into(self) -> AssetResult<ProcessingState<SequenceAsset>> {
+        Ok(ProcessingState::Loaded(self))
+    }
+}
+
+struct RunningSequence {
+    id: SequenceId,
+    owner: Option<Entity>,
+    // Taken out while the sequence is being stepped.
+    sequence: Option<Sequence>,
+    end: Option<SequenceEvent>,
+}
+
+/// A resource running `Sequence`s, which are stepped once per frame by the application after the
+/// timers.
+///
+/// A `SequenceEvent` is sent to the `EventChannel<SequenceEvent>` resource when a sequence stops
+/// running.
+#[derive(Default)]
+pub struct SequenceRunner {
+    running: Vec<RunningSequence>,
+    registry: Registry,
+    next_id: u64,
+}
+
+impl SequenceRunner {
+    /// Creates a new `SequenceRunner`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts running a sequence, from the next frame on.
+    pub fn start(&mut self, sequence: Sequence) -> SequenceId {
+        self.start_with_owner(None, sequence)
+    }
+
+    /// Starts running a sequence for an entity, which is cancelled if the entity is deleted.
+    pub fn start_for(&mut self, entity: Entity, sequence: Sequence) -> SequenceId {
+        self.start_with_owner(Some(entity), sequence)
+    }
+
+    /// Cancels a sequence, so that none of its steps run anymore.
+    ///
+    /// # Returns
+    ///
+    /// False if the sequence wasn't running anymore.
+    pub fn cancel(&mut self, id: SequenceId) -> bool {
+        match self
+            .running
+            .iter_mut()
+            .find(|running| running.id == id && running.end.is_none())
+        {
+            Some(running) => {
+                running.end = Some(SequenceEvent::Cancelled(id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels all sequences.
+    pub fn cancel_all(&mut self) {
+        for running in self.running.iter_mut().filter(|r| r.end.is_none()) {
+            running.end = Some(SequenceEvent::Cancelled(running.id));
+        }
+    }
+
+    /// Returns true if the sequence is still running.
+    pub fn is_running(&self, id: SequenceId) -> bool {
+        self.running
+            .iter()
+            .any(|running| running.id == id && running.end.is_none())
+    }
+
+    /// Registers an action which can be run by name, e.g. from a `SequenceAsset`. An action
+    /// registered with the same name is replaced.
+    pub fn add_action<N, F>(&mut self, name: N, action: F)
+    where
+        N: Into<String>,
+        F: FnMut(&mut World) + Send + Sync + 'static,
+    {
+        self.registry.actions.insert(name.into(), Box::new(action));
+    }
+
+    /// Registers a condition which can be waited for by name, e.g. from a `SequenceAsset`. A
+    /// condition registered with the same name is replaced.
+    pub fn add_condition<N, F>(&mut self, name: N, condition: F)
+    where
+        N: Into<String>,
+        F: FnMut(&World) -> bool + Send + Sync + 'static,
+    {
+        self.registry
+            .conditions
+            .insert(name.into(), Box::new(condition));
+    }
+
+    fn start_with_owner(&mut self, owner: Option<Entity>, sequence: Sequence) -> SequenceId {
+        let id = SequenceId(self.next_id);
+        self.next_id += 1;
+        self.running.push(RunningSequence {
+            id,
+            owner,
+            sequence: Some(sequence),
+            end: None,
+        });
+        id
+    }
+
+    /// Steps the running sequences of the `SequenceRunner` resource with the game time of the
+    /// frame, and sends the `SequenceEvent`s of the sequences which stopped.
+    ///
+    /// Sequences started while stepping only run from the next frame on.
+    pub(crate) fn update(world: &mut World) {
+        let delta = world.read_resource::<Time>().delta_seconds();
+        let (count, mut registry) = {
+            let mut runner = world.write_resource::<SequenceRunner>();
+            let registry = mem::replace(&mut runner.registry, Registry::default());
+            (runner.running.len(), registry)
+        };
+
+        for index in 0..count {
+            let (owner, sequence) = {
+                let mut runner = world.write_resource::<SequenceRunner>();
+                let running = &mut runner.running[index];
+                if running.end.is_some() {
+                    continue;
+                }
+                (running.owner, running.sequence.take())
+            };
+            let mut sequence = match sequence {
+                Some(sequence) => sequence,
+                None => continue,
+            };
+            let alive = owner.map_or(true, |owner| world.entities().is_alive(owner));
+            let finished = alive && sequence.step(world, delta, &mut registry);
+
+            // Sequences are only removed below, so the index is still valid.
+            let mut runner = world.write_resource::<SequenceRunner>();
+            let running = &mut runner.running[index];
+            if !alive {
+                running.end = Some(SequenceEvent::Cancelled(running.id));
+            } else if finished && running.end.is_none() {
+                running.end = Some(SequenceEvent::Finished(running.id));
+            }
+            running.sequence = Some(sequence);
+        }
+
+        let events = {
+            let mut runner = world.write_resource::<SequenceRunner>();
+            let added = mem::replace(&mut runner.registry, registry);
+            runner.registry.extend(added);
+            let events = runner
+                .running
+                .iter()
+                .filter_map(|running| running.end)
+                .collect::<Vec<_>>();
+            runner.running.retain(|running| running.end.is_none());
+            events
+        };
+        world
+            .write_resource::<EventChannel<SequenceEvent>>()
+            .iter_write(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use ecs::prelude::Builder;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.add_resource(SequenceRunner::default());
+        world.add_resource(EventChannel::<SequenceEvent>::new());
+        world.add_resource(Vec::<&'static str>::new());
+        world
+    }
+
+    fn next_frame(world: &mut World) {
+        world
+            .write_resource::<Time>()
+            .set_delta_time(Duration::from_millis(500));
+        SequenceRunner::update(world);
+    }
+
+    fn log(name: &'static str) -> impl FnMut(&mut World) + Send + Sync + 'static {
+        move |world: &mut World| world.write_resource::<Vec<&'static str>>().push(name)
+    }
+
+    #[test]
+    fn steps_wait_and_repeat() {
+        let mut world = world();
+        let mut reader = world
+            .write_resource::<EventChannel<SequenceEvent>>()
+            .register_reader();
+        let id = {
+            let mut runner = world.write_resource::<SequenceRunner>();
+            runner.add_action("named", log("named"));
+            let asset = SequenceAsset {
+                steps: vec![
+                    SequenceStep::Action("named".to_owned()),
+                    SequenceStep::Repeat(Some(2), vec![SequenceStep::Wait(0.5)]),
+                ],
+            };
+            runner.start(
+                Sequence::new()
+                    .run(log("start"))
+                    .wait(0.75)
+                    .wait_for_event(|event: &u32| *event == 7)
+                    .repeat(Some(0), Sequence::new().run(log("skipped")))
+                    .repeat(Some(2), Sequence::from(&asset)),
+            )
+        };
+
+        next_frame(&mut world);
+        assert_eq!(vec!["start"], *world.read_resource::<Vec<&'static str>>());
+        next_frame(&mut world);
+        world.write_resource::<EventChannel<u32>>().single_write(7);
+        next_frame(&mut world);
+        assert_eq!(
+            vec!["start", "named"],
+            *world.read_resource::<Vec<&'static str>>()
+        );
+        next_frame(&mut world);
+        next_frame(&mut world);
+        assert!(world.read_resource::<SequenceRunner>().is_running(id));
+        next_frame(&mut world);
+        assert!(!world.read_resource::<SequenceRunner>().is_running(id));
+        assert_eq!(
+            vec!["start", "named", "named"],
+            *world.read_resource::<Vec<&'static str>>()
+        );
+        assert_eq!(
+            vec![SequenceEvent::Finished(id)],
+            world
+                .read_resource::<EventChannel<SequenceEvent>>()
+                .read(&mut reader)
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn cancelled_with_owner() {
+        let mut world = world();
+        let entity = world.create_entity().build();
+        let (owned, cancelled) = {
+            let mut runner = world.write_resource::<SequenceRunner>();
+            let owned = runner.start_for(entity, Sequence::new().wait(10.).run(log("owned")));
+            let cancelled = runner.start(Sequence::new().wait(0.25).run(log("cancelled")));
+            (owned, cancelled)
+        };
+        assert!(world.write_resource::<SequenceRunner>().cancel(cancelled));
+        world.delete_entity(entity).unwrap();
+
+        next_frame(&mut world);
+        let runner = world.read_resource::<SequenceRunner>();
+        assert!(!runner.is_running(owned));
+        assert!(!runner.is_running(cancelled));
+        assert!(world.read_resource::<Vec<&'static str>>().is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+//! Functions run when the application shuts down.
+
+use std::mem;
+
+use ecs::prelude::World;
+
+/// The type of a shutdown hook.
+/// See `ShutdownHooks` for more details.
+pub type ShutdownHook = Box<FnMut(&mut World) + Send + Sync>;
+
+/// A resource holding functions to run when the application shuts down, once all states have been
+/// stopped.
+///
+/// Hooks are run in the order they were added, whether the application stopped because of a
+/// `Trans::Quit`, the window being closed or a termination signal. Systems added by bundles can
+/// register hooks in their `setup` method, e.g. to flush logs, save settings or close sockets.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fn setup(&mut self, res: &mut Resources) {
+///     Self::SystemData::setup(res);
+///     res.fetch_mut::<ShutdownHooks>().add("save_settings", |world| {
+///         world.read_resource::<Settings>().save();
+///     });
+/// }
+/// ```
+#[derive(Default)]
+pub struct ShutdownHooks {
+    hooks: Vec<(String, ShutdownHook)>,
+}
+
+impl ShutdownHooks {
+    /// Creates a new empty `ShutdownHooks`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a hook that runs when the application shuts down, after the previously added ones.
+    ///
+    /// The name is only used for logging.
+    pub fn add<N, F>(&mut self, name: N, hook: F)
+    where
+        N: Into<String>,
+        F: FnMut(&mut World) + Send + Sync + 'static,
+    {
+        self.hooks.push((name.into(), Box::new(hook)));
+    }
+
+    /// Returns the number of hooks that have not been run yet.
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Returns true if there are no hooks left to run.
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Removes all hooks from the `ShutdownHooks` resource of the world and runs them in order.
+    pub(crate) fn run(world: &mut World) {
+        let hooks = mem::replace(&mut world.write_resource::<ShutdownHooks>().hooks, Vec::new());
+        for (name, mut hook) in hooks {
+            debug!("Running shutdown hook `{}`", name);
+            hook(world);
+        }
+    }
+}
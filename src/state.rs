@@ -1,10 +1,11 @@
 //! Utilities for game state management.
 
 use amethyst_input::is_close_requested;
+use core::shrev::EventChannel;
 use ecs::prelude::World;
 use std::fmt::Result as FmtResult;
 use std::fmt::{Display, Formatter};
-use {GameData, StateEvent};
+use {DataInit, GameData, GameDataBuilder, StateEvent};
 
 /// Error type for errors occurring in StateMachine
 #[derive(Debug)]
@@ -57,8 +58,72 @@ pub enum Trans<T, E> {
     Push(Box<State<T, E>>),
     /// Remove the current state on the stack and insert a different one.
     Switch(Box<State<T, E>>),
+    /// Remove the given number of states from the top of the stack and push a new state onto it.
+    ///
+    /// The states below the removed ones are neither resumed nor paused again, so this can be
+    /// used to leave a nested flow of states in one step.
+    Replace(usize, Box<State<T, E>>),
+    /// Remove states from the stack until the active state matches the given predicate, then
+    /// resume it.
+    ///
+    /// If no state on the stack matches the predicate, the stack is left untouched. Predicates
+    /// usually match `State::name`, which the states to pop to must override.
+    PopTo(Box<Fn(&State<T, E>) -> bool>),
+    /// Apply each of the given transitions in order, without updating any state in between.
+    Sequence(Vec<Trans<T, E>>),
     /// Stop and remove all states and shut down the engine.
     Quit,
+    /// Stop and remove all states and shut down the engine, returning the given exit code from
+    /// `CoreApplication::run`.
+    QuitWith(i32),
+}
+
+/// The kind of transition that caused a `StateLifecycleEvent`.
+///
+/// Transitions applied through `Trans::Sequence` are reported individually.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransKind {
+    /// The state machine was started.
+    Initial,
+    /// `Trans::Pop`
+    Pop,
+    /// `Trans::Push`
+    Push,
+    /// `Trans::Switch`
+    Switch,
+    /// `Trans::Replace`
+    Replace,
+    /// `Trans::PopTo`
+    PopTo,
+    /// `Trans::Quit` or `Trans::QuitWith`, or the application shutting the state machine down.
+    Quit,
+}
+
+/// The lifecycle method that was called on a state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateLifecycle {
+    /// `State::on_start` was called.
+    Start,
+    /// `State::on_stop` was called.
+    Stop,
+    /// `State::on_pause` was called.
+    Pause,
+    /// `State::on_resume` was called.
+    Resume,
+}
+
+/// Event published to the `EventChannel<StateLifecycleEvent>` resource every time the
+/// `StateMachine` starts, stops, pauses or resumes a state.
+///
+/// This allows systems to react to state changes without the states notifying them by hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateLifecycleEvent {
+    /// The name of the state, see `State::name`.
+    pub state: &'static str,
+    /// The lifecycle method that was called on the state.
+    pub lifecycle: StateLifecycle,
+    /// The transition that caused the lifecycle method to be called.
+    pub trans: TransKind,
 }
 
 /// Event queue to trigger state `Trans` from other places than a `State`'s methods.
@@ -79,6 +144,31 @@ pub type SimpleTrans<'a, 'b> = Trans<GameData<'a, 'b>, StateEvent>;
 
 /// A trait which defines game states that can be used by the state machine.
 pub trait State<T, E: Send + Sync + 'static> {
+    /// Returns the name of this state, used to describe the state stack, in
+    /// `StateLifecycleEvent`s and to find states with `Trans::PopTo`.
+    ///
+    /// Defaults to `"State"` for every state type, as type names aren't available on stable
+    /// Rust. Override it in the states which need to be told apart, e.g. to use them with
+    /// `Trans::PopTo`.
+    fn name(&self) -> &'static str {
+        "State"
+    }
+
+    /// Returns the builder for systems that only run while this state is active.
+    ///
+    /// This is called by the [StateMachine](struct.StateMachine.html) right before `on_start`.
+    /// The systems are set up with the world's resources, dispatched after every `update` of
+    /// this state, and dropped once the state is stopped.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        None
+    }
+
+    /// Whether the systems returned by `dispatcher` keep running while this state is paused.
+    /// Defaults to `false`.
+    fn shadow_dispatch(&self) -> bool {
+        false
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<T>) {}
 
@@ -123,6 +213,31 @@ pub trait State<T, E: Send + Sync + 'static> {
 
 /// An empty `State` trait. It contains no `StateData` or custom `StateEvent`.
 pub trait EmptyState {
+    /// Returns the name of this state, used to describe the state stack, in
+    /// `StateLifecycleEvent`s and to find states with `Trans::PopTo`.
+    ///
+    /// Defaults to `"State"` for every state type, as type names aren't available on stable
+    /// Rust. Override it in the states which need to be told apart, e.g. to use them with
+    /// `Trans::PopTo`.
+    fn name(&self) -> &'static str {
+        "State"
+    }
+
+    /// Returns the builder for systems that only run while this state is active.
+    ///
+    /// This is called by the [StateMachine](struct.StateMachine.html) right before `on_start`.
+    /// The systems are set up with the world's resources, dispatched after every `update` of
+    /// this state, and dropped once the state is stopped.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        None
+    }
+
+    /// Whether the systems returned by `dispatcher` keep running while this state is paused.
+    /// Defaults to `false`.
+    fn shadow_dispatch(&self) -> bool {
+        false
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<()>) {}
 
@@ -172,6 +287,21 @@ pub trait EmptyState {
 }
 
 impl<T: EmptyState> State<(), StateEvent> for T {
+    /// Returns the name of this state.
+    fn name(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Returns the builder for systems that only run while this state is active.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        self.dispatcher()
+    }
+
+    /// Whether the systems returned by `dispatcher` keep running while this state is paused.
+    fn shadow_dispatch(&self) -> bool {
+        self.shadow_dispatch()
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, data: StateData<()>) {
         self.on_start(data)
@@ -226,6 +356,31 @@ impl<T: EmptyState> State<(), StateEvent> for T {
 
 /// A simple `State` trait. It contains `GameData` as its `StateData` and no custom `StateEvent`.
 pub trait SimpleState<'a, 'b> {
+    /// Returns the name of this state, used to describe the state stack, in
+    /// `StateLifecycleEvent`s and to find states with `Trans::PopTo`.
+    ///
+    /// Defaults to `"State"` for every state type, as type names aren't available on stable
+    /// Rust. Override it in the states which need to be told apart, e.g. to use them with
+    /// `Trans::PopTo`.
+    fn name(&self) -> &'static str {
+        "State"
+    }
+
+    /// Returns the builder for systems that only run while this state is active.
+    ///
+    /// This is called by the [StateMachine](struct.StateMachine.html) right before `on_start`.
+    /// The systems are set up with the world's resources, dispatched after every `update` of
+    /// this state, and dropped once the state is stopped.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        None
+    }
+
+    /// Whether the systems returned by `dispatcher` keep running while this state is paused.
+    /// Defaults to `false`.
+    fn shadow_dispatch(&self) -> bool {
+        false
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<GameData>) {}
 
@@ -281,6 +436,21 @@ pub trait SimpleState<'a, 'b> {
 impl<'a, 'b, T: SimpleState<'a, 'b>> State<GameData<'a, 'b>, StateEvent> for T {
     //pub trait SimpleState<'a,'b>: State<GameData<'a,'b>,()> {
 
+    /// Returns the name of this state.
+    fn name(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Returns the builder for systems that only run while this state is active.
+    fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+        self.dispatcher()
+    }
+
+    /// Whether the systems returned by `dispatcher` keep running while this state is paused.
+    fn shadow_dispatch(&self) -> bool {
+        self.shadow_dispatch()
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, data: StateData<GameData>) {
         self.on_start(data)
@@ -346,6 +516,10 @@ pub struct StateMachine<'a, T, E> {
     running: bool,
     #[derivative(Debug = "ignore")]
     state_stack: Vec<Box<State<T, E> + 'a>>,
+    /// The dispatchers of the started states, in the same order as `state_stack`.
+    #[derivative(Debug = "ignore")]
+    dispatchers: Vec<Option<GameData<'static, 'static>>>,
+    exit_code: i32,
 }
 
 impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
@@ -354,6 +528,8 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         StateMachine {
             running: false,
             state_stack: vec![Box::new(initial_state)],
+            dispatchers: Vec::new(),
+            exit_code: 0,
         }
     }
 
@@ -362,14 +538,38 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         self.running
     }
 
+    /// Returns the exit code set by `Trans::QuitWith`, or 0 if none was set.
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// Returns the number of states on the state stack.
+    pub fn depth(&self) -> usize {
+        self.state_stack.len()
+    }
+
+    /// Returns the names of the states on the state stack, from the bottom of the stack to the
+    /// active state.
+    ///
+    /// States which don't override [State::name](trait.State.html#method.name) are all listed as
+    /// `"State"`.
+    pub fn state_names(&self) -> Vec<&'static str> {
+        self.state_stack.iter().map(|state| state.name()).collect()
+    }
+
     /// Initializes the state machine.
     pub fn start(&mut self, data: StateData<T>) -> Result<(), StateError> {
         if !self.running {
-            let state = self
-                .state_stack
-                .last_mut()
-                .ok_or(StateError::NoStatesPresent)?;
-            state.on_start(data);
+            let StateData { world, data } = data;
+            if self.state_stack.is_empty() {
+                return Err(StateError::NoStatesPresent);
+            }
+            self.build_dispatcher(world);
+
+            //The stack was checked to be non-empty above
+            let state = self.state_stack.last_mut().unwrap();
+            state.on_start(StateData { world, data });
+            publish(world, state.name(), StateLifecycle::Start, TransKind::Initial);
             self.running = true;
         }
         Ok(())
@@ -415,6 +615,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             for state in self.state_stack.iter_mut() {
                 state.shadow_update(StateData { world, data });
             }
+            self.dispatch(world);
 
             self.transition(trans, StateData { world, data });
         }
@@ -432,7 +633,47 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                 Trans::Pop => self.pop(data),
                 Trans::Push(state) => self.push(state, data),
                 Trans::Switch(state) => self.switch(state, data),
+                Trans::Replace(count, state) => self.replace(count, state, data),
+                Trans::PopTo(predicate) => self.pop_to(predicate, data),
+                Trans::Sequence(transitions) => {
+                    let StateData { world, data } = data;
+                    for trans in transitions {
+                        self.transition(trans, StateData { world, data });
+                    }
+                }
                 Trans::Quit => self.stop(data),
+                Trans::QuitWith(exit_code) => {
+                    self.exit_code = exit_code;
+                    self.stop(data);
+                }
+            }
+        }
+    }
+
+    /// Builds the dispatcher of the state on top of the stack, if it has one.
+    fn build_dispatcher(&mut self, world: &mut World) {
+        let dispatcher = self
+            .state_stack
+            .last_mut()
+            .and_then(|state| state.dispatcher())
+            .map(|builder| builder.build(world));
+        self.dispatchers.push(dispatcher);
+    }
+
+    /// Runs the dispatcher of the active state, as well as the dispatchers of paused states that
+    /// requested shadow dispatching.
+    fn dispatch(&mut self, world: &World) {
+        let active = self.state_stack.len().saturating_sub(1);
+        for (index, (state, dispatcher)) in self
+            .state_stack
+            .iter()
+            .zip(self.dispatchers.iter_mut())
+            .enumerate()
+        {
+            if let Some(dispatcher) = dispatcher.as_mut() {
+                if index == active || state.shadow_dispatch() {
+                    dispatcher.update(world);
+                }
             }
         }
     }
@@ -443,13 +684,17 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             let StateData { world, data } = data;
             if let Some(mut state) = self.state_stack.pop() {
                 state.on_stop(StateData { world, data });
+                self.dispatchers.pop();
+                publish(world, state.name(), StateLifecycle::Stop, TransKind::Switch);
             }
 
             self.state_stack.push(state);
+            self.build_dispatcher(world);
 
             //State was just pushed, thus pop will always succeed
             let state = self.state_stack.last_mut().unwrap();
             state.on_start(StateData { world, data });
+            publish(world, state.name(), StateLifecycle::Start, TransKind::Switch);
         }
     }
 
@@ -459,13 +704,82 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             let StateData { world, data } = data;
             if let Some(state) = self.state_stack.last_mut() {
                 state.on_pause(StateData { world, data });
+                publish(world, state.name(), StateLifecycle::Pause, TransKind::Push);
             }
 
             self.state_stack.push(state);
+            self.build_dispatcher(world);
 
             //State was just pushed, thus pop will always succeed
             let state = self.state_stack.last_mut().unwrap();
             state.on_start(StateData { world, data });
+            publish(world, state.name(), StateLifecycle::Start, TransKind::Push);
+        }
+    }
+
+    /// Stops and removes `count` states from the top of the stack and pushes a new state onto
+    /// it, without resuming or pausing the states below.
+    fn replace(&mut self, count: usize, state: Box<State<T, E>>, data: StateData<T>) {
+        if self.running {
+            let StateData { world, data } = data;
+            if count == 0 {
+                if let Some(state) = self.state_stack.last_mut() {
+                    state.on_pause(StateData { world, data });
+                    publish(world, state.name(), StateLifecycle::Pause, TransKind::Replace);
+                }
+            }
+
+            for _ in 0..count {
+                match self.state_stack.pop() {
+                    Some(mut state) => {
+                        state.on_stop(StateData { world, data });
+                        self.dispatchers.pop();
+                        publish(world, state.name(), StateLifecycle::Stop, TransKind::Replace);
+                    }
+                    None => break,
+                }
+            }
+
+            self.state_stack.push(state);
+            self.build_dispatcher(world);
+
+            //State was just pushed, thus pop will always succeed
+            let state = self.state_stack.last_mut().unwrap();
+            state.on_start(StateData { world, data });
+            publish(world, state.name(), StateLifecycle::Start, TransKind::Replace);
+        }
+    }
+
+    /// Stops and removes states until the active state matches the predicate, and un-pauses it.
+    fn pop_to(&mut self, predicate: Box<Fn(&State<T, E>) -> bool>, data: StateData<T>) {
+        if self.running {
+            let target = match self
+                .state_stack
+                .iter()
+                .rposition(|state| predicate(state.as_ref()))
+            {
+                Some(index) => index,
+                None => {
+                    warn!("`Trans::PopTo` did not match any state on the stack, ignoring it");
+                    return;
+                }
+            };
+
+            let StateData { world, data } = data;
+            if target + 1 < self.state_stack.len() {
+                while self.state_stack.len() > target + 1 {
+                    if let Some(mut state) = self.state_stack.pop() {
+                        state.on_stop(StateData { world, data });
+                        self.dispatchers.pop();
+                        publish(world, state.name(), StateLifecycle::Stop, TransKind::PopTo);
+                    }
+                }
+
+                if let Some(state) = self.state_stack.last_mut() {
+                    state.on_resume(StateData { world, data });
+                    publish(world, state.name(), StateLifecycle::Resume, TransKind::PopTo);
+                }
+            }
         }
     }
 
@@ -476,10 +790,13 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             let StateData { world, data } = data;
             if let Some(mut state) = self.state_stack.pop() {
                 state.on_stop(StateData { world, data });
+                self.dispatchers.pop();
+                publish(world, state.name(), StateLifecycle::Stop, TransKind::Pop);
             }
 
             if let Some(state) = self.state_stack.last_mut() {
                 state.on_resume(StateData { world, data });
+                publish(world, state.name(), StateLifecycle::Resume, TransKind::Pop);
             } else {
                 self.running = false;
             }
@@ -492,6 +809,8 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             let StateData { world, data } = data;
             while let Some(mut state) = self.state_stack.pop() {
                 state.on_stop(StateData { world, data });
+                self.dispatchers.pop();
+                publish(world, state.name(), StateLifecycle::Stop, TransKind::Quit);
             }
 
             self.running = false;
@@ -499,9 +818,24 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     }
 }
 
+/// Writes a `StateLifecycleEvent` to the world, if the event channel for it has been added.
+fn publish(world: &World, state: &'static str, lifecycle: StateLifecycle, trans: TransKind) {
+    if let Some(mut channel) = world
+        .res
+        .try_fetch_mut::<EventChannel<StateLifecycleEvent>>()
+    {
+        channel.single_write(StateLifecycleEvent {
+            state,
+            lifecycle,
+            trans,
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ecs::prelude::{System, Write};
 
     struct State1(u8);
     struct State2;
@@ -541,4 +875,178 @@ mod tests {
         sm.update(StateData::new(&mut world, &mut ()));
         assert!(!sm.is_running());
     }
+
+    struct Named(&'static str);
+
+    impl State<(), ()> for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    fn push(name: &'static str) -> Trans<(), ()> {
+        Trans::Push(Box::new(Named(name)))
+    }
+
+    #[test]
+    fn state_names_lists_stack_from_bottom() {
+        use ecs::prelude::World;
+
+        let mut world = World::new();
+
+        let mut sm = StateMachine::new(Named("game"));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(push("pause"), StateData::new(&mut world, &mut ()));
+
+        assert_eq!(2, sm.depth());
+        assert_eq!(vec!["game", "pause"], sm.state_names());
+        assert_eq!(
+            vec!["State"],
+            StateMachine::<(), ()>::new(State2).state_names()
+        );
+    }
+
+    #[test]
+    fn sequence_replace_pop_to() {
+        use ecs::prelude::World;
+
+        let mut world = World::new();
+
+        let mut sm = StateMachine::new(Named("game"));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Sequence(vec![push("pause"), push("options"), push("controls")]),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(
+            vec!["game", "pause", "options", "controls"],
+            sm.state_names()
+        );
+
+        sm.transition(
+            Trans::Replace(2, Box::new(Named("audio"))),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(vec!["game", "pause", "audio"], sm.state_names());
+
+        sm.transition(
+            Trans::PopTo(Box::new(|state| state.name() == "missing")),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(3, sm.depth());
+
+        sm.transition(
+            Trans::PopTo(Box::new(|state| state.name() == "game")),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(vec!["game"], sm.state_names());
+        assert!(sm.is_running());
+    }
+
+    #[test]
+    fn quit_with_sets_exit_code() {
+        use ecs::prelude::World;
+
+        let mut world = World::new();
+
+        let mut sm = StateMachine::new(Named("game"));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        assert_eq!(0, sm.exit_code());
+
+        sm.transition(Trans::QuitWith(3), StateData::new(&mut world, &mut ()));
+        assert!(!sm.is_running());
+        assert_eq!(0, sm.depth());
+        assert_eq!(3, sm.exit_code());
+    }
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    struct CountSystem;
+
+    impl<'a> System<'a> for CountSystem {
+        type SystemData = Write<'a, Counter>;
+
+        fn run(&mut self, mut counter: Self::SystemData) {
+            counter.0 += 1;
+        }
+    }
+
+    struct Counting(bool);
+
+    impl State<(), ()> for Counting {
+        fn dispatcher(&mut self) -> Option<GameDataBuilder<'static, 'static>> {
+            Some(GameDataBuilder::default().with(CountSystem, "count", &[]))
+        }
+
+        fn shadow_dispatch(&self) -> bool {
+            self.0
+        }
+    }
+
+    fn count_updates(shadow: bool) -> u32 {
+        use core::ArcThreadPool;
+        use ecs::prelude::World;
+        use rayon::ThreadPoolBuilder;
+        use std::sync::Arc;
+
+        let mut world = World::new();
+        let pool: ArcThreadPool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        world.add_resource(pool);
+
+        let mut sm = StateMachine::new(Counting(shadow));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.update(StateData::new(&mut world, &mut ()));
+        sm.transition(push("pause"), StateData::new(&mut world, &mut ()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        sm.transition(Trans::Pop, StateData::new(&mut world, &mut ()));
+        sm.update(StateData::new(&mut world, &mut ()));
+
+        let count = world.read_resource::<Counter>().0;
+        count
+    }
+
+    #[test]
+    fn state_dispatcher_runs_while_active() {
+        assert_eq!(2, count_updates(false));
+        assert_eq!(3, count_updates(true));
+    }
+
+    #[test]
+    fn lifecycle_events_are_published() {
+        use core::shrev::EventChannel;
+        use ecs::prelude::World;
+
+        let mut world = World::new();
+        world.add_resource(EventChannel::<StateLifecycleEvent>::new());
+        let mut reader = world
+            .write_resource::<EventChannel<StateLifecycleEvent>>()
+            .register_reader();
+
+        let mut sm = StateMachine::new(Named("game"));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(push("pause"), StateData::new(&mut world, &mut ()));
+        sm.transition(Trans::Pop, StateData::new(&mut world, &mut ()));
+
+        let event = |state, lifecycle, trans| StateLifecycleEvent {
+            state,
+            lifecycle,
+            trans,
+        };
+        let events = world
+            .read_resource::<EventChannel<StateLifecycleEvent>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                event("game", StateLifecycle::Start, TransKind::Initial),
+                event("game", StateLifecycle::Pause, TransKind::Push),
+                event("pause", StateLifecycle::Start, TransKind::Push),
+                event("pause", StateLifecycle::Stop, TransKind::Pop),
+                event("game", StateLifecycle::Resume, TransKind::Pop),
+            ],
+            events
+        );
+    }
 }
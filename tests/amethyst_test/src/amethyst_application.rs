@@ -195,7 +195,12 @@ where
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
-        let params = (self.bundle_add_fns, self.resource_add_fns, self.state_fns);
+        let params = (
+            self.bundle_add_fns,
+            self.resource_add_fns,
+            self.state_fns,
+            self.render,
+        );
         Self::build_internal(params)
     }
 
@@ -208,10 +213,11 @@ where
     // parameters which causes a compilation failure.
     #[allow(unknown_lints, type_complexity)]
     fn build_internal(
-        (bundle_add_fns, resource_add_fns, state_fns): (
+        (bundle_add_fns, resource_add_fns, state_fns, render): (
             Vec<BundleAddFn>,
             Vec<FnResourceAdd>,
             Vec<FnState<GameData<'static, 'static>, E>>,
+            bool,
         ),
     ) -> Result<CoreApplication<'static, GameData<'static, 'static>, E, R>>
     where
@@ -229,20 +235,28 @@ where
             .into_iter()
             .rev()
             .for_each(|state_fn| states.push(state_fn.call()));
-        Self::build_application(SequencerState::new(states), game_data, resource_add_fns)
+        Self::build_application(
+            SequencerState::new(states),
+            game_data,
+            resource_add_fns,
+            render,
+        )
     }
 
     fn build_application<S>(
         first_state: S,
         game_data: GameDataBuilder<'static, 'static>,
         resource_add_fns: Vec<FnResourceAdd>,
+        render: bool,
     ) -> Result<CoreApplication<'static, GameData<'static, 'static>, E, R>>
     where
         S: State<GameData<'static, 'static>, E> + 'static,
         for<'b> R: EventReader<'b, Event = E>,
     {
+        // Applications without the `RenderBundle` have no window, so they are run headless.
         let mut application_builder =
-            CoreApplication::build(AmethystApplication::assets_dir(), first_state)?;
+            CoreApplication::build(AmethystApplication::assets_dir(), first_state)?
+                .headless(!render);
         {
             let world = &mut application_builder.world;
             for mut function in resource_add_fns {
@@ -260,9 +274,13 @@ where
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
-        let params = (self.bundle_add_fns, self.resource_add_fns, self.state_fns);
-
         let render = self.render;
+        let params = (
+            self.bundle_add_fns,
+            self.resource_add_fns,
+            self.state_fns,
+            render,
+        );
 
         // Run in a sub thread due to mesa's threading issues with GL software rendering
         // See: <https://users.rust-lang.org/t/trouble-identifying-cause-of-segfault/18096>